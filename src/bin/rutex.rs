@@ -1,16 +1,28 @@
-use clap::{arg, Parser, ValueEnum};
-use rutex;
-fn print_greeting_line() {
-    print!("rutex {} (", rutex::build_info::VERSION);
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, ValueEnum};
+use rutex::{
+    interaction::{self, History, Terminal},
+    parser::lexer::TexFile,
+    parsing::Input,
+    TexState,
+};
+/// The banner shown when the program starts and at the top of the
+/// transcript.
+fn greeting_line() -> String {
+    let mut line = format!("rutex {} (", rutex::build_info::VERSION);
     if let Some(tag) = rutex::build_info::GIT_TAG {
-        print!("tag: {}", tag);
+        line.push_str(&format!("tag: {}", tag));
     } else {
-        print!("git hash {}", rutex::build_info::GIT_HASH);
+        line.push_str(&format!("git hash {}", rutex::build_info::GIT_HASH));
     }
     if rutex::build_info::HAS_CHANGES {
-        print!(", with uncommitted changes");
+        line.push_str(", with uncommitted changes");
     }
-    println!(")");
+    line.push(')');
+    line
 }
 
 #[derive(ValueEnum, Debug, Clone)]
@@ -21,6 +33,17 @@ enum InteractionMode {
     ErrorStopMode,
 }
 
+impl From<InteractionMode> for interaction::InteractionMode {
+    fn from(mode: InteractionMode) -> Self {
+        match mode {
+            InteractionMode::Batch => Self::Batch,
+            InteractionMode::NonStop => Self::NonStop,
+            InteractionMode::Scroll => Self::Scroll,
+            InteractionMode::ErrorStopMode => Self::ErrorStop,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 struct Options {
     /// Activate debug mode
@@ -29,19 +52,84 @@ struct Options {
     #[arg(short, long)]
     interaction_mode: Option<InteractionMode>,
 
+    /// A directory in which to look for input files, after the current one
+    #[arg(short = 'I', value_name = "DIR")]
+    include: Vec<PathBuf>,
+
     /// The input file to process
     file: Option<String>,
 }
 
-fn main() {
-    print_greeting_line();
+fn main() -> ExitCode {
+    let greeting = greeting_line();
+    println!("{}", greeting);
     let opts = Options::parse();
-    let mut parser = rutex::parsing::Parser::new();
-    println!("{:?}", &opts);
-    parser.input.push(if let Some(file) = opts.file {
-        rutex::parsing::Input::new_from_file(&file, std::fs::File::open(file.clone()).unwrap())
+    if opts.verbose {
+        println!("{:?}", &opts);
+    }
+    let mut state = TexState::new();
+    if let Some(mode) = opts.interaction_mode.clone() {
+        state.interaction = mode.into();
+    }
+    // Directories given with -I come before those in TEXINPUTS.
+    state.search_path = opts.include.clone();
+    if let Some(paths) = std::env::var_os("TEXINPUTS") {
+        state
+            .search_path
+            .extend(std::env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+    let file = if let Some(file) = opts.file.clone() {
+        state.terminal = Some(Terminal::stdio());
+        match state.open_input_file(&file) {
+            Ok(file) => file,
+            Err(e) => {
+                state.fatal_error(e);
+                return ExitCode::FAILURE;
+            }
+        }
     } else {
-        rutex::parsing::Input::new_from_stdin()
-    });
-    parser.parse_token();
+        // The document is read from stdin, so there is nothing left to
+        // answer error prompts with.
+        state.terminal = Some(Terminal::new(
+            Box::new(std::io::empty()),
+            Box::new(std::io::stdout()),
+        ));
+        TexFile::new_from_input(Input::new_from_stdin())
+    };
+    // Like TeX, the job is named after the first input file.
+    let job_name = match opts.file {
+        Some(_) => Path::new(file.path())
+            .file_stem()
+            .map_or("texput".to_string(), |s| s.to_string_lossy().into_owned()),
+        None => "texput".to_string(),
+    };
+    let log_name = format!("{}.log", job_name);
+    match std::fs::File::create(&log_name) {
+        Ok(log) => state.open_log(Box::new(BufWriter::new(log)), &greeting),
+        Err(e) => eprintln!("! I can't write on file `{}' ({}).", log_name, e),
+    }
+    if let Err(e) = state.add_file(file) {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+    // Errors have been reported by the time the job ends because of one.
+    if state.parse_and_execute().is_ok() {
+        for message in state.final_cleanup() {
+            state.print_line(&message);
+        }
+    }
+    if state.log.is_some() {
+        state.close_log();
+        state.print_line(&format!("Transcript written on {}.", log_name));
+    }
+    if opts.verbose {
+        for line in state.memory_usage() {
+            println!("{}", line);
+        }
+    }
+    if state.history >= History::ErrorMessageIssued {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
 }
@@ -0,0 +1,17 @@
+use crate::scanning::arithmetic::Scaled;
+
+/// Whether a box was built in horizontal or vertical mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxKind {
+    Horizontal,
+    Vertical,
+}
+
+/// A box as stored in a box register. Only its dimensions are kept for now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TexBox {
+    pub kind: BoxKind,
+    pub width: Scaled,
+    pub height: Scaled,
+    pub depth: Scaled,
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The level of the outermost group. Global assignments are made at this
+/// level, and entries that have never been assigned count as being at it.
+pub const LEVEL_ONE: u16 = 1;
+
+/// A value together with the group level at which it was assigned, like an
+/// entry of TeX's `eqtb`.
+#[derive(Clone, Debug)]
+pub struct Entry<V> {
+    value: V,
+    level: u16,
+}
+
+/// One part of the table of equivalents, e.g. all category codes. Only
+/// entries that differ from the initial values are stored, so lookups and
+/// assignments are single hash map operations regardless of the group
+/// nesting depth.
+#[derive(Clone, Debug)]
+pub struct Table<K, V> {
+    entries: HashMap<K, Entry<V>>,
+}
+
+impl<K: Hash + Eq, V> Table<K, V> {
+    pub fn new() -> Self {
+        Table {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|e| &e.value)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|e| &mut e.value)
+    }
+
+    /// Assigns `value` to `key` in the group at `level`, or globally. Returns
+    /// the previous entry if it has to be put on the save stack, which is
+    /// the case for the first local assignment to `key` in a group.
+    pub fn assign(
+        &mut self,
+        key: K,
+        value: V,
+        level: u16,
+        global: bool,
+    ) -> Option<Option<Entry<V>>> {
+        let level = if global { LEVEL_ONE } else { level };
+        let previous_level = self.entries.get(&key).map_or(LEVEL_ONE, |e| e.level);
+        let previous = self.entries.insert(key, Entry { value, level });
+        if global || previous_level == level {
+            None
+        } else {
+            Some(previous)
+        }
+    }
+
+    /// Puts back an entry saved by [`Table::assign`] when a group ends,
+    /// unless a global assignment has been made to `key` since.
+    pub fn restore(&mut self, key: K, saved: Option<Entry<V>>) {
+        if self.entries.get(&key).is_some_and(|e| e.level == LEVEL_ONE) {
+            return;
+        }
+        match saved {
+            Some(entry) => self.entries.insert(key, entry),
+            None => self.entries.remove(&key),
+        };
+    }
+}
+
+impl<K: Hash + Eq, V> Default for Table<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Table<K, V> {
+    /// A table with the given initial values.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Table {
+            entries: iter
+                .into_iter()
+                .map(|(k, value)| {
+                    (
+                        k,
+                        Entry {
+                            value,
+                            level: LEVEL_ONE,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
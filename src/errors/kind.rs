@@ -0,0 +1,336 @@
+/// The classes of errors, following the error messages of TeX. Each has a
+/// stable numeric code, for tools that need to tell errors apart without
+/// matching on their messages, and the help lines TeX gives for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input has ended. This is how a job normally ends.
+    EndOfFile,
+    /// Something that should not have happened, i.e. a bug.
+    Internal,
+
+    // Input files.
+    FileNotFound,
+    InvalidUtf8,
+    IoError,
+    /// "Text line contains an invalid character".
+    InvalidCharacter,
+
+    // Expansion and macros.
+    /// "Undefined control sequence".
+    UndefinedControlSequence,
+    /// "File ended while scanning use of \a", with a runaway argument or
+    /// definition.
+    Runaway,
+    /// "Forbidden control sequence found while scanning use of \a".
+    ForbiddenControlSequence,
+    /// "Paragraph ended before \a was complete".
+    ParagraphEnded,
+    /// "Argument of \a has an extra }".
+    ExtraRightBraceInArgument,
+    /// "Use of \a doesn't match its definition".
+    DoesNotMatchDefinition,
+    /// "Missing control sequence inserted".
+    MissingControlSequence,
+    /// "Missing { inserted".
+    MissingLeftBrace,
+    /// "You already have nine parameters".
+    TooManyParameters,
+    /// "Parameters must be numbered consecutively".
+    NonConsecutiveParameters,
+    /// "Illegal parameter number in definition of \a".
+    IllegalParameterNumber,
+    /// "You can't use a prefix with `x'".
+    PrefixNotAllowed,
+    /// "You can't use `\long' or `\outer' with `x'".
+    LongOrOuterNotAllowed,
+    /// "Missing \endcsname inserted".
+    MissingEndCsName,
+    /// "Extra \endcsname".
+    ExtraEndCsName,
+    /// "Incomplete \if...; all text was ignored after line n".
+    IncompleteConditional,
+    /// "Missing = inserted for \ifnum".
+    MissingRelation,
+    /// "Extra \or", "Extra \else" or "Extra \fi".
+    ExtraConditional,
+
+    // Numbers and quantities.
+    /// "Missing number, treated as zero".
+    MissingNumber,
+    /// "Number too big".
+    NumberTooBig,
+    /// "Bad register code" and the other numbers out of their range.
+    BadNumber,
+    /// "Invalid code (n), should be between 0 and 15".
+    InvalidCode,
+    /// "Improper alphabetic constant".
+    ImproperAlphabeticConstant,
+    /// "Illegal unit of measure (pt inserted)".
+    IllegalUnit,
+    /// "Incompatible glue units".
+    IncompatibleGlueUnits,
+    /// "Dimension too large".
+    DimensionTooLarge,
+    /// "You can't use `x' after \the".
+    CantUseAfterThe,
+
+    // Groups and modes.
+    /// "Too many }'s".
+    TooManyRightBraces,
+    /// "Extra }, or forgotten \endgroup".
+    ExtraRightBrace,
+    /// "Extra \endgroup".
+    ExtraEndGroup,
+    /// "Missing } inserted", or a missing `\endgroup` or `$`.
+    MissingGroupEnd,
+    /// "Missing $ inserted".
+    MissingDollar,
+    /// "You can't use `x' in vertical mode".
+    CantUseInMode,
+
+    // Messages from the document.
+    /// An error message given by `\errmessage`.
+    UserError,
+    /// Not really an error: something shown by `\show`, `\showthe`,
+    /// `\showbox` or `\showlists`, after which TeX stops as for an error.
+    Shown,
+
+    // Ending the job.
+    /// "TeX capacity exceeded".
+    Overflow,
+    /// The job was ended from the error prompt or after too many errors.
+    JobAborted,
+}
+
+impl ErrorKind {
+    /// The code of the error class, which stays the same between versions:
+    /// 1xx for input files, 2xx for expansion and macros, 3xx for numbers,
+    /// 4xx for groups and modes, 5xx for messages from the document and 9xx
+    /// for errors that end the job.
+    pub fn code(&self) -> u16 {
+        match self {
+            ErrorKind::EndOfFile => 0,
+            ErrorKind::Internal => 1,
+            ErrorKind::FileNotFound => 101,
+            ErrorKind::InvalidUtf8 => 102,
+            ErrorKind::IoError => 103,
+            ErrorKind::InvalidCharacter => 104,
+            ErrorKind::UndefinedControlSequence => 201,
+            ErrorKind::Runaway => 202,
+            ErrorKind::ForbiddenControlSequence => 203,
+            ErrorKind::ParagraphEnded => 204,
+            ErrorKind::ExtraRightBraceInArgument => 205,
+            ErrorKind::DoesNotMatchDefinition => 206,
+            ErrorKind::MissingControlSequence => 207,
+            ErrorKind::MissingLeftBrace => 208,
+            ErrorKind::TooManyParameters => 209,
+            ErrorKind::NonConsecutiveParameters => 210,
+            ErrorKind::IllegalParameterNumber => 211,
+            ErrorKind::PrefixNotAllowed => 212,
+            ErrorKind::LongOrOuterNotAllowed => 213,
+            ErrorKind::MissingEndCsName => 214,
+            ErrorKind::ExtraEndCsName => 215,
+            ErrorKind::IncompleteConditional => 216,
+            ErrorKind::MissingRelation => 217,
+            ErrorKind::ExtraConditional => 218,
+            ErrorKind::MissingNumber => 301,
+            ErrorKind::NumberTooBig => 302,
+            ErrorKind::BadNumber => 303,
+            ErrorKind::InvalidCode => 304,
+            ErrorKind::ImproperAlphabeticConstant => 305,
+            ErrorKind::IllegalUnit => 306,
+            ErrorKind::IncompatibleGlueUnits => 307,
+            ErrorKind::DimensionTooLarge => 308,
+            ErrorKind::CantUseAfterThe => 309,
+            ErrorKind::TooManyRightBraces => 401,
+            ErrorKind::ExtraRightBrace => 402,
+            ErrorKind::ExtraEndGroup => 403,
+            ErrorKind::MissingGroupEnd => 404,
+            ErrorKind::MissingDollar => 405,
+            ErrorKind::CantUseInMode => 406,
+            ErrorKind::UserError => 501,
+            ErrorKind::Shown => 502,
+            ErrorKind::Overflow => 901,
+            ErrorKind::JobAborted => 902,
+        }
+    }
+
+    /// The help lines TeX shows for errors of this class.
+    pub fn help(&self) -> &'static [&'static str] {
+        match self {
+            ErrorKind::EndOfFile | ErrorKind::JobAborted => &[],
+            ErrorKind::Internal => &[
+                "This should not have happened, so please report it",
+                "along with the input that caused it.",
+            ],
+            ErrorKind::FileNotFound => &[
+                "The file is looked for in the current directory and",
+                "then in each directory of the input search path.",
+            ],
+            ErrorKind::InvalidUtf8 => &[
+                "The line contains bytes that are not valid UTF-8.",
+                "I'll read the part of it before them and go on",
+                "with the next line.",
+            ],
+            ErrorKind::IoError => &["The operating system would not let me read the file."],
+            ErrorKind::InvalidCharacter => &[
+                "A funny symbol that I can't read has just been input.",
+                "Continue, and I'll forget that it ever happened.",
+            ],
+            ErrorKind::UndefinedControlSequence => &[
+                "The control sequence at the end of the top line",
+                "of your error message was never \\def'ed. If you have",
+                "misspelled it (e.g., `\\hobx'), type `I' and the correct",
+                "spelling (e.g., `I\\hbox'). Otherwise just continue,",
+                "and I'll forget about whatever was undefined.",
+            ],
+            ErrorKind::Runaway | ErrorKind::ForbiddenControlSequence => &[
+                "I suspect you have forgotten a `}', causing me",
+                "to read past where you wanted me to stop.",
+                "I'll try to recover; but if the error is serious,",
+                "you'd better type `E' or `X' now and fix your file.",
+            ],
+            ErrorKind::ParagraphEnded => &[
+                "I suspect you've forgotten a `}', causing me to apply this",
+                "control sequence to too much text. How can we recover?",
+                "My plan is to forget the whole thing and hope for the best.",
+            ],
+            ErrorKind::ExtraRightBraceInArgument => &[
+                "I've run across a `}' that doesn't seem to match anything.",
+                "For example, `\\def\\a#1{...}' and `\\a}' would produce",
+                "this error. If you simply proceed now, the `\\par' that",
+                "I've just inserted will cause me to report a runaway",
+                "argument that might be the root of the problem. But if",
+                "your `}' was spurious, just type `2' and it will go away.",
+            ],
+            ErrorKind::DoesNotMatchDefinition => &[
+                "If you say, e.g., `\\def\\a1{...}', then you must always",
+                "put `1' after `\\a', since control sequence names are",
+                "made up of letters only. The macro here has not been",
+                "followed by the required stuff, so I'm ignoring it.",
+            ],
+            ErrorKind::MissingControlSequence => &[
+                "Please don't say `\\def cs{...}', say `\\def\\cs{...}'.",
+                "I've inserted an inaccessible control sequence so that your",
+                "definition will be completed without mixing me up too badly.",
+                "You can recover graciously from this error, if you're",
+                "careful; see exercise 27.2 in The TeXbook.",
+            ],
+            ErrorKind::MissingLeftBrace => &[
+                "A left brace was mandatory here, so I've put one in.",
+                "You might want to delete and/or insert some corrections",
+                "so that I will find a matching right brace soon.",
+                "(If you're confused by all this, try typing `I}' now.)",
+            ],
+            ErrorKind::TooManyParameters => &["I'm going to ignore the # sign you just used."],
+            ErrorKind::NonConsecutiveParameters => &[
+                "I've inserted the digit you should have used after the #.",
+                "Type `1' to delete what you did use.",
+            ],
+            ErrorKind::IllegalParameterNumber => &[
+                "You meant to type ## instead of #, right?",
+                "Or maybe a } was forgotten somewhere earlier, and things",
+                "are all screwed up? I'm going to assume that you meant ##.",
+            ],
+            ErrorKind::PrefixNotAllowed => {
+                &["I'll pretend you didn't say \\long or \\outer or \\global or \\protected."]
+            }
+            ErrorKind::LongOrOuterNotAllowed => {
+                &["I'll pretend you didn't say \\long or \\outer or \\protected here."]
+            }
+            ErrorKind::MissingEndCsName => &[
+                "The control sequence marked <to be read again> should",
+                "not appear between \\csname and \\endcsname.",
+            ],
+            ErrorKind::ExtraEndCsName => &["I'm ignoring this, since I wasn't doing a \\csname."],
+            ErrorKind::IncompleteConditional => &[
+                "A forbidden control sequence occurred in skipped text.",
+                "This kind of error happens when you say `\\if...' and forget",
+                "the matching `\\fi'. I've inserted a `\\fi'; this might work.",
+            ],
+            ErrorKind::MissingRelation => &["I was expecting to see `<', `=', or `>'. Didn't."],
+            ErrorKind::ExtraConditional => &["I'm ignoring this; it doesn't match any \\if."],
+            ErrorKind::MissingNumber => &[
+                "A number should have been here; I inserted `0'.",
+                "(If you can't figure out why I needed to see a number,",
+                "look up `weird error' in the index to The TeXbook.)",
+            ],
+            ErrorKind::NumberTooBig => &[
+                "I can only go up to 2147483647='17777777777=\"7FFFFFFF,",
+                "so I'm using that number instead of yours.",
+            ],
+            ErrorKind::BadNumber => &[
+                "The number is outside the range allowed here,",
+                "so I changed it to zero.",
+            ],
+            ErrorKind::InvalidCode => &["I'm going to use 0 instead of that illegal code value."],
+            ErrorKind::ImproperAlphabeticConstant => &[
+                "A one-character control sequence belongs after a ` mark.",
+                "So I'm essentially inserting \\0 here.",
+            ],
+            ErrorKind::IllegalUnit => &[
+                "Dimensions can be in units of em, ex, in, pt, pc,",
+                "cm, mm, dd, cc, nd, nc, bp, or sp; but yours is a new one!",
+                "I'll assume that you meant to say pt, for printer's points.",
+                "To recover gracefully from this error, it's best to",
+                "delete the erroneous units; e.g., type `2' to delete",
+                "two letters. (See Chapter 27 of The TeXbook.)",
+            ],
+            ErrorKind::IncompatibleGlueUnits => {
+                &["I'm going to assume that 1mu=1pt when they're mixed."]
+            }
+            ErrorKind::DimensionTooLarge => &[
+                "I can't work with sizes bigger than about 19 feet.",
+                "Continue and I'll use the largest value I can.",
+            ],
+            ErrorKind::CantUseAfterThe => &["I'm forgetting what you said and using zero instead."],
+            ErrorKind::TooManyRightBraces => &[
+                "You've closed more groups than you opened.",
+                "Such booboos are generally harmless, so keep going.",
+            ],
+            ErrorKind::ExtraRightBrace => &[
+                "I've deleted a group-closing symbol because it seems to be",
+                "spurious, as in `$x}$'. But perhaps the } is legitimate and",
+                "you forgot something else, as in `\\hbox{$x}'. In such cases",
+                "the way to recover is to insert both the forgotten and the",
+                "deleted material, e.g., by typing `I$}'.",
+            ],
+            ErrorKind::ExtraEndGroup => {
+                &["Things are pretty mixed up, but I think the worst is over."]
+            }
+            ErrorKind::MissingGroupEnd => &[
+                "I've inserted something that you may have forgotten. (See the",
+                "<inserted text> above.) With luck, this will get me unwedged.",
+                "But if you really didn't forget anything, try typing `2' now; then",
+                "my insertion and my current dilemma will both disappear.",
+            ],
+            ErrorKind::MissingDollar => &[
+                "I've inserted a begin-math/end-math symbol since I think",
+                "you left one out. Proceed, with fingers crossed.",
+            ],
+            ErrorKind::CantUseInMode => &[
+                "Sorry, but I'm not programmed to handle this case;",
+                "I'll just pretend that you didn't ask for it.",
+                "If you're in the wrong mode, you might be able to",
+                "return to the right one by typing `I}' or `I$' or `I\\par'.",
+            ],
+            ErrorKind::UserError => &[
+                "This error message was generated by an \\errmessage",
+                "command, so I can't give any explicit help.",
+                "Pretend that you're Hercule Poirot: Examine all clues,",
+                "and deduce the truth by order and method.",
+            ],
+            ErrorKind::Shown => &[
+                "This isn't an error message; I'm just \\showing something.",
+                "Type `I\\show...' to show more (e.g., \\show\\cs,",
+                "\\showthe\\count10, \\showbox255, \\showlists).",
+                "And type `I\\tracingonline=1\\show...' to show boxes and",
+                "lists on your terminal as well as in the transcript file.",
+            ],
+            ErrorKind::Overflow => &[
+                "If you really absolutely need more capacity,",
+                "you can ask a wizard to enlarge me.",
+            ],
+        }
+    }
+}
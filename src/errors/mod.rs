@@ -1,34 +1,39 @@
 use std::{fmt::Display, num::ParseIntError};
 
-#[derive(Debug)]
-pub enum ErrorKind {
-    UnknownError,
-    UnknownTokenError,
-    UnknownMacroError,
-    EndOfFile,
-    ParseError,
-}
+use crate::constants::{ERROR_LINE, HALF_ERROR_LINE};
+
+mod kind;
+pub use kind::ErrorKind;
+
 #[derive(Debug)]
 pub struct Error {
     location: Option<(String, usize, usize)>,
     kind: ErrorKind,
     message: String,
+    /// The lines showing where in the input the error happened, as printed
+    /// by TeX's `show_context`.
+    context: Vec<String>,
+    /// The help lines shown when the user asks for help.
+    help: Vec<String>,
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some((filename, row, column)) = &self.location {
             f.write_str(
                 format!(
-                    "{filename}:{row}:{column} [{:?}] {}",
-                    self.kind, self.message
+                    "{filename}:{row}:{column} [E{:03}] {}",
+                    self.kind.code(),
+                    self.message
                 )
                 .as_str(),
             )?;
-            Ok(())
         } else {
-            f.write_str(format!("[{:?}] {}", self.kind, self.message).as_str())?;
-            Ok(())
+            f.write_str(format!("[E{:03}] {}", self.kind.code(), self.message).as_str())?;
         }
+        for line in &self.context {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
     }
 }
 impl Error {
@@ -41,6 +46,8 @@ impl Error {
             location,
             kind,
             message,
+            context: Vec::new(),
+            help: help_lines(kind.help()),
         }
     }
     pub fn new(kind: ErrorKind, message: String) -> Self {
@@ -48,6 +55,54 @@ impl Error {
             location: None,
             kind,
             message,
+            context: Vec::new(),
+            help: help_lines(kind.help()),
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn help(&self) -> &[String] {
+        &self.help
+    }
+
+    /// Replaces the help lines of the error class with more specific ones.
+    pub fn with_help(mut self, help: &[&str]) -> Self {
+        self.help = help_lines(help);
+        self
+    }
+
+    /// Whether the job has to end after the error, rather than continue
+    /// after it has been reported.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::Overflow | ErrorKind::JobAborted | ErrorKind::IoError
+        )
+    }
+
+    pub fn location(&self) -> Option<&(String, usize, usize)> {
+        self.location.as_ref()
+    }
+
+    pub fn context(&self) -> &[String] {
+        &self.context
+    }
+
+    /// Records where the error happened, keeping a location or context
+    /// that is already known.
+    pub fn locate(&mut self, location: Option<(String, usize, usize)>, context: Vec<String>) {
+        if self.location.is_none() {
+            self.location = location;
+        }
+        if self.context.is_empty() {
+            self.context = context;
         }
     }
 
@@ -56,11 +111,43 @@ impl Error {
             location: None,
             kind: ErrorKind::EndOfFile,
             message: "End of file reached".to_string(),
+            context: Vec::new(),
+            help: Vec::new(),
         }
     }
 }
+fn help_lines(help: &[&str]) -> Vec<String> {
+    help.iter().map(|l| l.to_string()).collect()
+}
+
 impl From<ParseIntError> for Error {
     fn from(value: ParseIntError) -> Self {
-        Error::new(ErrorKind::ParseError, value.to_string())
+        Error::new(ErrorKind::BadNumber, value.to_string())
+    }
+}
+
+/// Formats one level of context as TeX does: `before`, the text read so far,
+/// on the first line and `after`, the text still to be read, on the second
+/// line below its end. The first line is cut at the start to at most
+/// `HALF_ERROR_LINE` characters and the second at the end to keep both within
+/// `ERROR_LINE` characters.
+pub fn context_lines(before: &str, after: &str) -> [String; 2] {
+    let before: Vec<char> = before.chars().collect();
+    let after: Vec<char> = after.chars().collect();
+    let mut first = String::new();
+    if before.len() > HALF_ERROR_LINE {
+        first.push_str("...");
+        first.extend(&before[before.len() - HALF_ERROR_LINE + 3..]);
+    } else {
+        first.extend(&before);
+    }
+    let n = first.chars().count();
+    let mut second = " ".repeat(n);
+    if n + after.len() > ERROR_LINE {
+        second.extend(&after[..ERROR_LINE.saturating_sub(n + 3)]);
+        second.push_str("...");
+    } else {
+        second.extend(&after);
     }
+    [first, second]
 }
@@ -0,0 +1,445 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    constants::MAX_PRINT_LINE,
+    errors::{Error, ErrorKind},
+    macros::messages::SHOWN_OK,
+    parameters::IntegerParameter,
+    parser::{input_stack::TokenListKind, lexer::TexFile},
+    TexState,
+};
+
+mod printer;
+pub use printer::{format_date, Printer};
+
+/// How much the user is asked about errors, from least to most, as set by
+/// `\batchmode`, `\nonstopmode`, `\scrollmode` and `\errorstopmode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InteractionMode {
+    /// Nothing is shown on the terminal and errors are not stopped at.
+    Batch,
+    /// Errors are shown but not stopped at, and reading from the terminal
+    /// ends the job.
+    NonStop,
+    /// Errors are shown but not stopped at.
+    Scroll,
+    /// The user is asked what to do after every error.
+    ErrorStop,
+}
+
+impl InteractionMode {
+    pub const ALL: [InteractionMode; 4] = [
+        InteractionMode::Batch,
+        InteractionMode::NonStop,
+        InteractionMode::Scroll,
+        InteractionMode::ErrorStop,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InteractionMode::Batch => r"\batchmode",
+            InteractionMode::NonStop => r"\nonstopmode",
+            InteractionMode::Scroll => r"\scrollmode",
+            InteractionMode::ErrorStop => r"\errorstopmode",
+        }
+    }
+}
+
+/// How bad the worst problem of the run so far was, like TeX's `history`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum History {
+    Spotless,
+    WarningIssued,
+    ErrorMessageIssued,
+    FatalErrorStop,
+}
+
+/// The terminal, from which the user's answers are read and on which
+/// messages are shown.
+pub struct Terminal {
+    input: Box<dyn BufRead>,
+    output: Printer,
+}
+
+impl Terminal {
+    pub fn new(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Terminal {
+            input,
+            output: Printer::new(output),
+        }
+    }
+
+    pub fn stdio() -> Self {
+        Self::new(
+            Box::new(std::io::BufReader::new(std::io::stdin())),
+            Box::new(std::io::stdout()),
+        )
+    }
+
+    /// Reads a line typed by the user, without its line break. Returns
+    /// `None` at the end of the input.
+    fn read_line(&mut self) -> std::io::Result<Option<String>> {
+        self.output.flush();
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.output.line_ended();
+        Ok(Some(line.trim_end().to_string()))
+    }
+}
+
+/// The menu shown for an answer the error prompt does not understand.
+const ERROR_MENU: [&str; 5] = [
+    "Type <return> to proceed, S to scroll future error messages,",
+    "R to run without stopping, Q to run quietly,",
+    "I to insert something, ",
+    "1 or ... or 9 to ignore the next 1 to 9 tokens of input,",
+    "H for help, X to quit.",
+];
+
+fn job_aborted(message: &str) -> Error {
+    Error::new(ErrorKind::JobAborted, message.to_string())
+}
+
+impl TexState {
+    /// The printers that output currently goes to: the terminal unless in
+    /// batch mode or printing diagnostics, and the transcript.
+    fn printers(&mut self) -> impl Iterator<Item = &mut Printer> {
+        let terminal = match &mut self.terminal {
+            Some(t) if self.interaction > InteractionMode::Batch && !self.log_only => {
+                Some(&mut t.output)
+            }
+            _ => None,
+        };
+        terminal.into_iter().chain(self.log.as_mut())
+    }
+
+    /// Prints `s` on the terminal, unless in batch mode, and in the
+    /// transcript.
+    pub fn print(&mut self, s: &str) {
+        for printer in self.printers() {
+            printer.print(s);
+        }
+    }
+
+    /// Ends the current line.
+    pub fn print_ln(&mut self) {
+        for printer in self.printers() {
+            printer.print_ln();
+        }
+    }
+
+    /// Prints `s` at the start of a line, like TeX's `print_nl`.
+    pub fn print_nl(&mut self, s: &str) {
+        for printer in self.printers() {
+            printer.start_line();
+            printer.print(s);
+        }
+    }
+
+    /// Prints the text of a `\message`, which starts a new line if it would
+    /// not fit on the current one and is separated by a space otherwise.
+    pub fn print_message(&mut self, s: &str) {
+        let length = s.chars().count();
+        for printer in self.printers() {
+            if printer.offset() + length > MAX_PRINT_LINE - 2 {
+                printer.print_ln();
+            } else if printer.offset() > 0 {
+                printer.print(" ");
+            }
+            printer.print(s);
+            printer.flush();
+        }
+    }
+
+    /// Prints `line` on a line of its own.
+    pub fn print_line(&mut self, line: &str) {
+        self.print_nl(line);
+        self.print_ln();
+    }
+
+    /// Prints `line` on a line of its own in the transcript only.
+    pub fn log_line(&mut self, line: &str) {
+        if let Some(log) = &mut self.log {
+            log.start_line();
+            log.print(line);
+            log.print_ln();
+        }
+    }
+
+    /// Starts the transcript, which is written to `output`, with `banner`
+    /// and the current date.
+    pub fn open_log(&mut self, output: Box<dyn Write>, banner: &str) {
+        let mut log = Printer::new(output);
+        log.print(banner);
+        log.print_ln();
+        log.print(&format!(
+            "(INITEX)  {}",
+            format_date(std::time::SystemTime::now())
+        ));
+        log.print_ln();
+        self.log = Some(log);
+    }
+
+    /// Finishes the transcript.
+    pub fn close_log(&mut self) {
+        if let Some(mut log) = self.log.take() {
+            log.start_line();
+            log.flush();
+        }
+    }
+
+    /// Starts printing diagnostics such as those of `\tracingmacros`. They
+    /// only go to the transcript, if there is one, unless `\tracingonline`
+    /// is positive.
+    pub fn begin_diagnostic(&mut self) {
+        if self.diagnostics_in_log_only() {
+            self.log_only = true;
+            self.history = self.history.max(History::WarningIssued);
+        }
+    }
+
+    /// Whether diagnostics currently go to the transcript only.
+    fn diagnostics_in_log_only(&self) -> bool {
+        self.state.get_integer(IntegerParameter::TracingOnline) <= 0 && self.log.is_some()
+    }
+
+    /// Prints `s` on the terminal only, unless in batch mode.
+    fn print_on_terminal(&mut self, s: &str) {
+        if let Some(terminal) = &mut self.terminal {
+            if self.interaction > InteractionMode::Batch {
+                terminal.output.print(s);
+            }
+        }
+    }
+
+    /// Ends printing diagnostics, leaving a blank line if `blank_line` is
+    /// set.
+    pub fn end_diagnostic(&mut self, blank_line: bool) {
+        self.print_nl("");
+        if blank_line {
+            self.print_ln();
+        }
+        self.log_only = false;
+    }
+
+    /// Shows an error and its context.
+    fn print_error(&mut self, e: &Error) {
+        // What `\show` and friends show is printed as it is.
+        if let ErrorKind::Shown = e.kind() {
+            self.print_nl(e.message());
+            // Like TeX, point to the transcript on the terminal when a box
+            // or list was only shown there.
+            if e.message() == SHOWN_OK && self.diagnostics_in_log_only() {
+                self.print_on_terminal(" (see the transcript file)");
+            }
+            self.print(".");
+        } else {
+            self.print_nl(&format!("! {}.", e.message()));
+        }
+        self.print_ln();
+        for line in e.context() {
+            self.print_line(line);
+        }
+    }
+
+    /// Reads a line from the terminal after showing `prompt`, ending the job
+    /// if there is nothing to read from. The line is also written to the
+    /// transcript.
+    fn prompt_input(&mut self, prompt: &str) -> Result<String, Error> {
+        if self.interaction < InteractionMode::Scroll {
+            return Err(job_aborted(
+                r"*** (cannot \read from terminal in nonstop modes)",
+            ));
+        }
+        self.print(prompt);
+        let line = match &mut self.terminal {
+            Some(terminal) => terminal
+                .read_line()
+                .map_err(|e| Error::new(ErrorKind::IoError, e.to_string()))?,
+            None => None,
+        };
+        let line = line.ok_or_else(|| job_aborted(r"*** (job aborted, no legal \end found)"))?;
+        if let Some(log) = &mut self.log {
+            log.print(&line);
+            log.print_ln();
+        }
+        Ok(line)
+    }
+
+    /// Opens the input file `name`. As in TeX's `prompt_file_name`, the user
+    /// is asked for another name if it cannot be opened, and the job ends if
+    /// there is no one to ask. Without a terminal in `\errorstopmode` the
+    /// error is returned as it is.
+    pub fn open_input_file(&mut self, name: &str) -> Result<TexFile, Error> {
+        let mut name = name.to_string();
+        loop {
+            let mut e = match TexFile::open(&name, &self.search_path) {
+                Ok(file) => return Ok(file),
+                Err(e) => e,
+            };
+            if self.interaction == InteractionMode::ErrorStop && self.terminal.is_none() {
+                return Err(e);
+            }
+            self.history = self.history.max(History::ErrorMessageIssued);
+            e.locate(self.location(), self.show_context());
+            self.print_error(&e);
+            if self.interaction < InteractionMode::Scroll {
+                return Err(job_aborted("*** (job aborted, file error in nonstop mode)"));
+            }
+            name = self.prompt_input("Please type another input file name: ")?;
+        }
+    }
+
+    /// Reports an error that the job can go on after, like TeX's `error`
+    /// procedure. In `\errorstopmode` the user is asked what to do about it;
+    /// in the other modes the help lines only go to the transcript. Returns
+    /// an error if the job has to end, which is also the case in
+    /// `\errorstopmode` when there is no terminal to ask.
+    pub fn error(&mut self, e: Error) -> Result<(), Error> {
+        if self.interaction == InteractionMode::ErrorStop && self.terminal.is_none() {
+            return Err(e);
+        }
+        self.history = self.history.max(History::ErrorMessageIssued);
+        self.print_error(&e);
+        self.recover(&e).map_err(|e| self.fatal_error(e))
+    }
+
+    /// Reports `e`, which happened in the middle of a command, so that the
+    /// command can go on as TeX does after most errors. The end of the input
+    /// and errors that end the job are returned as they are.
+    pub fn recoverable_error(&mut self, mut e: Error) -> Result<(), Error> {
+        if matches!(e.kind(), ErrorKind::EndOfFile) || e.is_fatal() {
+            return Err(e);
+        }
+        e.locate(self.location(), self.show_context());
+        self.error(e)
+    }
+
+    fn recover(&mut self, e: &Error) -> Result<(), Error> {
+        if self.interaction == InteractionMode::ErrorStop {
+            return self.ask_user(e);
+        }
+        // Showing something does not count as an error.
+        if !matches!(e.kind(), ErrorKind::Shown) {
+            self.error_count += 1;
+        }
+        if self.error_count == 100 {
+            return Err(job_aborted("(That makes 100 errors; please try again.)"));
+        }
+        for line in e.help() {
+            self.log_line(line);
+        }
+        self.log_line("");
+        Ok(())
+    }
+
+    /// Reports an error after which the job cannot go on, like TeX's
+    /// `succumb`, and returns it.
+    pub fn fatal_error(&mut self, e: Error) -> Error {
+        // An error in the middle of a command may already have ended the job.
+        if self.history == History::FatalErrorStop {
+            return e;
+        }
+        self.history = History::FatalErrorStop;
+        if let ErrorKind::JobAborted = e.kind() {
+            self.print_line(e.message());
+        } else {
+            self.print_error(&e);
+        }
+        e
+    }
+
+    /// Asks the user what to do after the error `e` until they decide to
+    /// go on.
+    fn ask_user(&mut self, e: &Error) -> Result<(), Error> {
+        let mut help = e.help().to_vec();
+        loop {
+            let answer = self.prompt_input("? ")?;
+            let mut chars = answer.chars();
+            let Some(c) = chars.next() else {
+                return Ok(());
+            };
+            match c.to_ascii_uppercase() {
+                '0'..='9' => {
+                    let mut n = c.to_digit(10).unwrap_or(0);
+                    if let Some(d) = chars.next().and_then(|d| d.to_digit(10)) {
+                        n = n * 10 + d;
+                    }
+                    for _ in 0..n {
+                        self.get_element()?;
+                    }
+                    help = vec![
+                        "I have just deleted some text, as you asked.".to_string(),
+                        "You can now delete more, or insert, or whatever.".to_string(),
+                    ];
+                    for line in self.show_context() {
+                        self.print_line(&line);
+                    }
+                }
+                'H' => {
+                    if help.is_empty() {
+                        self.print_line("Sorry, I don't know how to help in this situation.");
+                        self.print_line("Maybe you should try asking a human?");
+                    } else {
+                        for line in &help {
+                            self.print_line(line);
+                        }
+                    }
+                    help = [
+                        "Sorry, I already gave what help I could...",
+                        "Maybe you should try asking a human?",
+                        "An error might have occurred before I noticed any problems.",
+                        "``If all else fails, read the instructions.''",
+                    ]
+                    .map(String::from)
+                    .to_vec();
+                }
+                'I' => {
+                    let mut text: String = chars.collect();
+                    if text.is_empty() {
+                        text = self.prompt_input("insert>")?;
+                    }
+                    return self.insert_text(text);
+                }
+                c @ ('Q' | 'R' | 'S') => {
+                    self.error_count = 0;
+                    self.interaction = match c {
+                        'Q' => InteractionMode::Batch,
+                        'R' => InteractionMode::NonStop,
+                        _ => InteractionMode::Scroll,
+                    };
+                    // The message is shown even when entering batch mode.
+                    let message = format!("OK, entering {}...", self.interaction.name());
+                    if let Some(terminal) = &mut self.terminal {
+                        terminal.output.print(&message);
+                        terminal.output.print_ln();
+                    }
+                    self.log_line(&message);
+                    return Ok(());
+                }
+                'X' => {
+                    self.interaction = InteractionMode::Scroll;
+                    return Err(job_aborted("*** (job aborted at your request)"));
+                }
+                _ => {
+                    for line in ERROR_MENU {
+                        self.print_line(line);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Inserts `text`, typed after an error, into the input. It is read
+    /// with the current category codes and no end of line character.
+    fn insert_text(&mut self, text: String) -> Result<(), Error> {
+        let mut file = TexFile::new_from_contents("insert".to_string(), text);
+        let mut tokens = Vec::new();
+        while let Some(t) = file.next_token(&|c| self.state.get_category(c), -1)? {
+            tokens.push(t);
+        }
+        self.push_tokens(TokenListKind::Inserted, tokens)
+    }
+}
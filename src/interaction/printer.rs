@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::constants::MAX_PRINT_LINE;
+
+/// Text output that breaks lines after `MAX_PRINT_LINE` characters, as TeX
+/// does on the terminal and in the transcript.
+pub struct Printer {
+    output: Box<dyn Write>,
+    /// The number of characters on the current line.
+    offset: usize,
+}
+
+impl Printer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Printer { output, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn print(&mut self, s: &str) {
+        // Like TeX, carry on if the output cannot be written to.
+        for c in s.chars() {
+            if c == '\n' {
+                self.print_ln();
+                continue;
+            }
+            let _ = write!(self.output, "{}", c);
+            self.offset += 1;
+            if self.offset == MAX_PRINT_LINE {
+                self.print_ln();
+            }
+        }
+    }
+
+    /// Ends the current line.
+    pub fn print_ln(&mut self) {
+        let _ = writeln!(self.output);
+        self.offset = 0;
+    }
+
+    /// Ends the current line unless nothing has been printed on it.
+    pub fn start_line(&mut self) {
+        if self.offset > 0 {
+            self.print_ln();
+        }
+    }
+
+    /// Notes that the current line was ended by something else, such as
+    /// the user pressing return on the terminal.
+    pub fn line_ended(&mut self) {
+        self.offset = 0;
+    }
+
+    pub fn flush(&mut self) {
+        let _ = self.output.flush();
+    }
+}
+
+/// The date and time as TeX shows them in the transcript, e.g.
+/// `18 OCT 2026 14:05`, in UTC.
+pub fn format_date(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ];
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, minutes) = (seconds / 86400, seconds % 86400 / 60);
+    // The civil date of a day count, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{} {} {} {:02}:{:02}",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        minutes / 60,
+        minutes % 60
+    )
+}
@@ -1,14 +1,29 @@
+use boxes::TexBox;
+use eqtb::{Entry, Table};
 use errors::Error;
-use macros::{Macro, MacroMap};
+use interaction::{History, InteractionMode, Printer, Terminal};
+use macros::{conditionals::Condition, show_tokens, Command, MacroMap};
+use parameters::{IntegerParameter, RegisterKind, TokenParameter};
 use parser::{
+    input_stack::{InputLevel, InputStack, TokenListKind},
     lexer::{CharacterCategory, CharacterMap, TexFile},
     parser::Token,
+    symbol::Symbol,
 };
+use scanning::Value;
+use std::path::PathBuf;
 
+pub mod boxes;
+pub mod build_info;
 pub mod document_generation;
+pub mod eqtb;
 pub mod errors;
+pub mod interaction;
 pub mod macros;
+pub mod parameters;
 pub mod parser;
+pub mod parsing;
+pub mod scanning;
 pub(crate) mod constants {
     #![allow(warnings)]
     pub const MEM_MAX: usize = 30000;
@@ -18,6 +33,7 @@ pub(crate) mod constants {
     pub const HALF_ERROR_LINE: usize = 42;
     pub const MAX_PRINT_LINE: usize = 79;
     pub const STACK_SIZE: usize = 200;
+    pub const NUMBER_OF_REGISTERS: usize = 32768;
     pub const MAX_IN_OPEN: usize = 6;
     pub const FONT_MAX: usize = 75;
     pub const FONT_MEM_SIZE: usize = 20000;
@@ -33,274 +49,647 @@ pub(crate) mod constants {
     pub const FILE_NAME_SIZE: usize = 40;
     pub const POOL_NAME: &'static str = "TeXformats:TEX.POOL                     ";
 }
+/// A saved equivalent, to be restored when the current group ends, or another
+/// entry of the save stack.
+#[derive(Clone, Debug)]
+enum SaveEntry {
+    /// The start of a group.
+    Boundary,
+    Category(char, Option<Entry<CharacterCategory>>),
+    Integer(IntegerParameter, Option<Entry<i32>>),
+    Tokens(TokenParameter, Option<Entry<Vec<Token>>>),
+    Register((RegisterKind, u16), Option<Entry<Value>>),
+    Box(u16, Option<Entry<Option<TexBox>>>),
+    Macro(Symbol, Option<Entry<Option<Command>>>),
+    /// A token saved by `\aftergroup`.
+    AfterGroup(Token),
+}
+
+/// The current values of everything that is subject to grouping, kept as in
+/// TeX: a flat table of equivalents, and a save stack holding the values to
+/// restore when groups end.
 #[derive(Clone, Debug)]
 pub struct TexGroupState {
-    parent: Option<Box<TexGroupState>>,
-    character_map: CharacterMap,
-    macro_map: MacroMap,
-    global_defs: bool,
+    categories: Table<char, CharacterCategory>,
+    macros: Table<Symbol, Option<Command>>,
+    integer_parameters: Table<IntegerParameter, i32>,
+    token_parameters: Table<TokenParameter, Vec<Token>>,
+    registers: Table<(RegisterKind, u16), Value>,
+    boxes: Table<u16, Option<TexBox>>,
+    /// The group nesting level, starting at [`eqtb::LEVEL_ONE`].
+    level: u16,
+    save_stack: Vec<SaveEntry>,
 }
 
 impl TexGroupState {
     pub fn initial() -> Self {
         TexGroupState {
-            parent: None,
-            character_map: CharacterMap::new_and_init(),
-            macro_map: MacroMap::new_and_init(),
-            global_defs: false,
+            categories: CharacterMap::new_and_init().into_iter().collect(),
+            macros: MacroMap::new_and_init().into_iter().collect(),
+            integer_parameters: Table::new(),
+            token_parameters: Table::new(),
+            registers: Table::new(),
+            boxes: Table::new(),
+            level: eqtb::LEVEL_ONE,
+            save_stack: Vec::new(),
         }
     }
 
-    pub fn child(self) -> Self {
-        TexGroupState {
-            character_map: CharacterMap::new(),
-            macro_map: MacroMap::new(),
-            global_defs: self.global_defs.clone(),
-            parent: Some(Box::new(self)),
-        }
+    pub fn get_category(&self, c: char) -> CharacterCategory {
+        self.categories
+            .get(&c)
+            .copied()
+            .unwrap_or(CharacterCategory::Other)
     }
 
-    pub fn get_category(&self, c: char) -> CharacterCategory {
-        match self.character_map.clone().get(c) {
-            Some(c) => c,
-            None => {
-                if let Some(p) = &self.parent {
-                    p.get_category(c)
-                } else {
-                    CharacterCategory::Other
-                }
-            }
+    pub fn get_integer(&self, p: IntegerParameter) -> i32 {
+        self.integer_parameters
+            .get(&p)
+            .copied()
+            .unwrap_or_else(|| p.default_value())
+    }
+
+    pub fn get_tokens(&self, p: TokenParameter) -> Vec<Token> {
+        self.token_parameters.get(&p).cloned().unwrap_or_default()
+    }
+
+    pub fn get_register(&self, kind: RegisterKind, n: u16) -> Value {
+        match self.registers.get(&(kind, n)) {
+            Some(v) => v.clone(),
+            None => kind.default_value(),
         }
     }
 
+    pub fn get_box(&self, n: u16) -> Option<&TexBox> {
+        self.boxes.get(&n).and_then(|b| b.as_ref())
+    }
+
     pub fn get_global_defs(&self) -> bool {
-        self.global_defs
-    }
-    pub fn set_global_defs(&mut self, b: bool) {
-        self.global_defs = b;
-    }
-    pub fn get_macro(&self, s: &String) -> Option<&Box<dyn Macro>> {
-        if let Some(s) = self.macro_map.get(s) {
-            Some(s)
-        } else if let Some(ref p) = self.parent {
-            p.get_macro(s)
-        } else {
-            None
-        }
+        self.get_integer(IntegerParameter::GlobalDefs) > 0
+    }
+    pub fn get_macro(&self, s: Symbol) -> Option<&Command> {
+        self.macros.get(&s).and_then(|m| m.as_ref())
     }
 
     pub fn set_category_with_global(&mut self, chr: char, cat: CharacterCategory, global: bool) {
-        if global {
-            if let Some(ref mut p) = self.parent {
-                return p.set_category_with_global(chr, cat, global);
-            }
+        if let Some(saved) = self.categories.assign(chr, cat, self.level, global) {
+            self.save_stack.push(SaveEntry::Category(chr, saved));
         }
-        self.character_map.set(chr, cat);
     }
 
-    pub fn set_macro_with_global(&mut self, r#macro: Box<dyn Macro>, global: bool) {
-        if global {
-            if let Some(ref mut p) = self.parent {
-                return p.set_macro_with_global(r#macro, global);
-            }
+    pub fn set_integer_with_global(&mut self, p: IntegerParameter, value: i32, global: bool) {
+        if let Some(saved) = self.integer_parameters.assign(p, value, self.level, global) {
+            self.save_stack.push(SaveEntry::Integer(p, saved));
+        }
+    }
+
+    pub fn set_tokens_with_global(&mut self, p: TokenParameter, tokens: Vec<Token>, global: bool) {
+        if let Some(saved) = self.token_parameters.assign(p, tokens, self.level, global) {
+            self.save_stack.push(SaveEntry::Tokens(p, saved));
+        }
+    }
+
+    pub fn set_register_with_global(
+        &mut self,
+        kind: RegisterKind,
+        n: u16,
+        value: Value,
+        global: bool,
+    ) {
+        if let Some(saved) = self.registers.assign((kind, n), value, self.level, global) {
+            self.save_stack.push(SaveEntry::Register((kind, n), saved));
+        }
+    }
+
+    pub fn set_box_with_global(&mut self, n: u16, b: Option<TexBox>, global: bool) {
+        if let Some(saved) = self.boxes.assign(n, b, self.level, global) {
+            self.save_stack.push(SaveEntry::Box(n, saved));
+        }
+    }
+
+    /// Removes the box from box register `n`, leaving it void. As in TeX this
+    /// changes the register at the level where it was set, bypassing the
+    /// grouping.
+    pub fn take_box(&mut self, n: u16) -> Option<TexBox> {
+        self.boxes.get_mut(&n).and_then(|b| b.take())
+    }
+
+    /// Gives the control sequence `name` the meaning `r#macro`, or makes it
+    /// undefined if that is `None`.
+    pub fn set_macro_with_global(&mut self, name: Symbol, r#macro: Option<Command>, global: bool) {
+        if let Some(saved) = self.macros.assign(name, r#macro, self.level, global) {
+            self.save_stack.push(SaveEntry::Macro(name, saved));
         }
-        let name = r#macro.name();
-        self.macro_map.set(name, r#macro);
     }
     pub fn set_category(&mut self, chr: char, cat: CharacterCategory) {
         self.set_category_with_global(chr, cat, self.get_global_defs());
     }
-    pub fn set_macro(&mut self, r#macro: Box<dyn Macro>) {
-        self.set_macro_with_global(r#macro, self.global_defs);
+    pub fn set_integer(&mut self, p: IntegerParameter, value: i32) {
+        self.set_integer_with_global(p, value, self.get_global_defs());
+    }
+    pub fn set_macro(&mut self, name: Symbol, r#macro: Option<Command>) {
+        self.set_macro_with_global(name, r#macro, self.get_global_defs());
+    }
+
+    /// Saves `t` to be read again when the current group ends. Outside of
+    /// any group the token is dropped, as it is in TeX.
+    pub fn after_group(&mut self, t: Token) {
+        if self.level > eqtb::LEVEL_ONE {
+            self.save_stack.push(SaveEntry::AfterGroup(t));
+        }
+    }
+    /// Starts a new group.
+    pub fn push(&mut self) {
+        self.save_stack.push(SaveEntry::Boundary);
+        self.level += 1;
+    }
+    /// Ends the current group, restoring the values saved in it, and returns
+    /// the tokens saved by `\aftergroup`. Returns `None` at the outermost
+    /// level.
+    pub fn pop(&mut self) -> Option<Vec<Token>> {
+        if self.level == eqtb::LEVEL_ONE {
+            return None;
+        }
+        self.level -= 1;
+        let mut after_group = Vec::new();
+        while let Some(entry) = self.save_stack.pop() {
+            match entry {
+                SaveEntry::Boundary => break,
+                SaveEntry::Category(c, saved) => self.categories.restore(c, saved),
+                SaveEntry::Integer(p, saved) => self.integer_parameters.restore(p, saved),
+                SaveEntry::Tokens(p, saved) => self.token_parameters.restore(p, saved),
+                SaveEntry::Register(r, saved) => self.registers.restore(r, saved),
+                SaveEntry::Box(n, saved) => self.boxes.restore(n, saved),
+                SaveEntry::Macro(name, saved) => self.macros.restore(name, saved),
+                SaveEntry::AfterGroup(t) => after_group.push(t),
+            }
+        }
+        after_group.reverse();
+        Some(after_group)
+    }
+}
+
+/// The prefixes (`\global`, ...) given to the command being executed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Prefixes {
+    pub global: bool,
+    pub long: bool,
+    pub outer: bool,
+    pub protected: bool,
+}
+
+/// TeX's semantic modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Vertical,
+    InternalVertical,
+    Horizontal,
+    RestrictedHorizontal,
+    Math,
+    DisplayMath,
+}
+
+impl Mode {
+    /// The name of the mode as TeX prints it, e.g. "vertical mode".
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Vertical => "vertical mode",
+            Mode::InternalVertical => "internal vertical mode",
+            Mode::Horizontal => "horizontal mode",
+            Mode::RestrictedHorizontal => "restricted horizontal mode",
+            Mode::Math => "math mode",
+            Mode::DisplayMath => "display math mode",
+        }
+    }
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Mode::Vertical | Mode::InternalVertical)
+    }
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Mode::Horizontal | Mode::RestrictedHorizontal)
+    }
+    pub fn is_math(self) -> bool {
+        matches!(self, Mode::Math | Mode::DisplayMath)
+    }
+    /// Whether this is one of the modes TeX calls inner: internal vertical,
+    /// restricted horizontal and non-display math mode.
+    pub fn is_inner(self) -> bool {
+        matches!(
+            self,
+            Mode::InternalVertical | Mode::RestrictedHorizontal | Mode::Math
+        )
     }
+}
+
+/// The kinds of groups, which TeX calls group codes. Each group has to be
+/// ended by the command that matches the one that started it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKind {
+    /// A group started by `{`.
+    Simple,
+    HBox,
+    AdjustedHBox,
+    VBox,
+    VTop,
+    Align,
+    NoAlign,
+    Output,
+    Math,
+    Disc,
+    Insert,
+    VCenter,
+    MathChoice,
+    /// A group started by `\begingroup`.
+    SemiSimple,
+    MathShift,
+    MathLeft,
+}
 
-    pub fn pop(self) -> Option<Self> {
-        if let Some(x) = self.parent {
-            Some(*x)
-        } else {
-            None
+impl GroupKind {
+    /// The name of the group as TeX shows it, e.g. `semi simple group`.
+    pub fn description(self) -> &'static str {
+        match self {
+            GroupKind::Simple => "simple group",
+            GroupKind::HBox => "hbox group",
+            GroupKind::AdjustedHBox => "adjusted hbox group",
+            GroupKind::VBox => "vbox group",
+            GroupKind::VTop => "vtop group",
+            GroupKind::Align => "align group",
+            GroupKind::NoAlign => "no align group",
+            GroupKind::Output => "output group",
+            GroupKind::Math => "math group",
+            GroupKind::Disc => "disc group",
+            GroupKind::Insert => "insert group",
+            GroupKind::VCenter => "vcenter group",
+            GroupKind::MathChoice => "math choice group",
+            GroupKind::SemiSimple => "semi simple group",
+            GroupKind::MathShift => "math shift group",
+            GroupKind::MathLeft => "math left group",
         }
     }
-    pub fn run_macro(&mut self, s: &String, state: &mut TexState) -> Result<(), Error> {
-        if let Some(m) = self.get_macro(&s.clone()) {
-            m.run(state)
-        } else {
-            Err(Error::new(
-                errors::ErrorKind::UnknownMacroError,
-                format!("Command {:?} not found", s),
-            ))
+    /// The token that ends the group when it is ended by something else.
+    fn closing(self) -> &'static str {
+        match self {
+            GroupKind::SemiSimple => r"\endgroup",
+            GroupKind::MathShift => "$",
+            GroupKind::MathLeft => r"\right.",
+            _ => "}",
         }
     }
 }
 
+/// An entry of the group stack.
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub kind: GroupKind,
+    /// The line on which the group started.
+    pub line: usize,
+}
+
 pub struct TexState {
-    pub files: Vec<TexFile>,
+    pub input: InputStack,
     pub state: TexGroupState,
+    pub prefixes: Prefixes,
+    pub mode: Mode,
+    /// The conditionals that are currently being processed, innermost last.
+    pub conditions: Vec<Condition>,
+    /// The groups that are currently open, innermost last.
+    pub groups: Vec<Group>,
+    /// The token saved by `\afterassignment`, which is read after the next
+    /// assignment.
+    pub after_assignment: Option<Token>,
+    /// The directories in which `\input` looks for files that are not found
+    /// in the current directory.
+    pub search_path: Vec<PathBuf>,
+    pub interaction: InteractionMode,
+    /// The terminal to show errors on and ask about them, if there is one.
+    pub terminal: Option<Terminal>,
+    /// Where the transcript is written, if it is.
+    pub log: Option<Printer>,
+    /// Whether output goes to the transcript only, as while printing
+    /// diagnostics.
+    log_only: bool,
+    /// The number of errors since the end of the last paragraph.
+    pub error_count: usize,
+    /// Whether the long help for `\errmessage` has already been given
+    /// without stopping.
+    long_help_seen: bool,
+    pub history: History,
 }
-unsafe impl Sync for TexState {}
-unsafe impl Send for TexState {}
 
 impl TexState {
     pub fn new() -> Self {
         TexState {
-            files: vec![],
+            input: InputStack::new(),
             state: TexGroupState::initial(),
+            prefixes: Prefixes::default(),
+            mode: Mode::Vertical,
+            conditions: Vec::new(),
+            groups: Vec::new(),
+            after_assignment: None,
+            search_path: Vec::new(),
+            interaction: InteractionMode::ErrorStop,
+            terminal: None,
+            log: None,
+            log_only: false,
+            error_count: 0,
+            long_help_seen: false,
+            history: History::Spotless,
         }
     }
-    pub fn add_file(&mut self, file: TexFile) {
-        self.files.push(file);
+    pub fn add_file(&mut self, file: TexFile) -> Result<(), Error> {
+        self.input.push_file(file)
     }
-    pub fn advance(&mut self) {
-        if let Some(file) = self.files.last_mut() {
-            if let Err(_) = file.advance(1) {
-                self.files.pop();
-            }
-        }
+    /// Inserts `tokens` in front of the remaining input, so that they are read
+    /// next in the given order.
+    pub fn push_tokens(&mut self, kind: TokenListKind, tokens: Vec<Token>) -> Result<(), Error> {
+        self.input.push_tokens(kind, tokens)
     }
-    #[inline]
-    pub fn advance_by(&mut self, n: usize) {
-        for _ in 0..n {
-            self.advance();
-        }
+    /// Puts a single token back onto the input.
+    pub fn back_input(&mut self, token: Token) -> Result<(), Error> {
+        self.input.back_input(token)
+    }
+    /// Reads the next token from the topmost input level, dropping levels
+    /// that have been exhausted.
+    pub fn get_element(&mut self) -> Result<Token, Error> {
+        Ok(self.get_next()?.0)
     }
-    pub fn read_character(&mut self) -> Option<char> {
-        if let Some(file) = self.files.last_mut() {
-            let m = file.get_current_char(0);
-            if let Err(_) = file.advance(1) {
-                self.files.pop();
+    /// Reads the next token like [`TexState::get_element`], also telling
+    /// whether it may be expanded, which it may not right after `\noexpand`.
+    pub fn get_next(&mut self) -> Result<(Token, bool), Error> {
+        let state = &self.state;
+        let end_line_char = state.get_integer(IntegerParameter::EndLineChar);
+        loop {
+            match self.input.current() {
+                None => return Err(Error::eof()),
+                Some(InputLevel::TokenList(list)) => {
+                    if let Some(t) = list.next_token() {
+                        return Ok((t, *list.kind() != TokenListKind::NoExpand));
+                    }
+                }
+                Some(InputLevel::File(file)) => {
+                    if let Some(t) = file.next_token(&|c| state.get_category(c), end_line_char)? {
+                        return Ok((t, true));
+                    }
+                }
             }
-            m
-        } else {
-            None
+            self.input.pop();
         }
     }
-    pub fn read_ahead_character(&mut self, n: usize) -> Option<char> {
-        if let Some(file) = self.files.last_mut() {
-            file.get_current_char(n)
-        } else {
-            None
+    /// Starts a group of the given kind.
+    pub fn push_group(&mut self, kind: GroupKind) {
+        let line = self.input.line();
+        self.groups.push(Group { kind, line });
+        self.state.push();
+    }
+    /// The innermost open group, or `None` at the bottom level.
+    pub fn current_group(&self) -> Option<GroupKind> {
+        self.groups.last().map(|g| g.kind)
+    }
+    /// Ends the innermost group, whatever its kind.
+    pub fn pop_group(&mut self) -> Result<(), Error> {
+        let after_group = self.state.pop().ok_or(Error::new(
+            errors::ErrorKind::Internal,
+            "No groups left to end".to_string(),
+        ))?;
+        self.groups.pop();
+        if !after_group.is_empty() {
+            self.push_tokens(TokenListKind::BackedUp, after_group)?;
         }
+        Ok(())
     }
-    #[inline]
-    fn get_category(&self, c: char) -> CharacterCategory {
-        self.state.get_category(c)
+    /// Handles a `}`, which ends a simple group.
+    fn handle_right_brace(&mut self) -> Result<(), Error> {
+        match self.current_group() {
+            None => Err(Error::new(
+                errors::ErrorKind::TooManyRightBraces,
+                "Too many }'s".to_string(),
+            )),
+            Some(kind @ (GroupKind::SemiSimple | GroupKind::MathShift | GroupKind::MathLeft)) => {
+                Err(Error::new(
+                    errors::ErrorKind::ExtraRightBrace,
+                    format!("Extra }}, or forgotten {}", kind.closing()),
+                ))
+            }
+            // There are no boxes or other lists to finish yet.
+            Some(_) => self.pop_group(),
+        }
     }
-    fn get_char_and_category(&mut self, n: usize) -> Option<(char, CharacterCategory)> {
-        let c = match self.read_ahead_character(n) {
-            Some(c) => c,
-            None => return None,
-        };
-        Some((c, self.get_category(c)))
-    }
-    fn get_control(&mut self) -> Result<Token, Error> {
-        if let Some(c) = self.read_character() {
-            let cat = self.get_category(c);
-            let mut command = String::new();
-            command.push('\\');
-            command.push(c);
-            if cat == CharacterCategory::Letter {
-                let mut n = 0;
-                let mut space = false;
-                loop {
-                    match self.get_char_and_category(n) {
-                        Some((c, CharacterCategory::Letter)) if !space => command.push(c),
-                        Some((_, CharacterCategory::Space)) => {
-                            if space {
-                            } else {
-                                space = true;
-                            }
-                        }
-                        _ => break,
-                    }
-                    n += 1;
-                }
-                for _ in 0..n {
-                    self.advance();
+    /// Handles an `\endgroup`, which ends a semi-simple group.
+    pub fn handle_end_group(&mut self) -> Result<(), Error> {
+        match self.current_group() {
+            Some(GroupKind::SemiSimple) => self.pop_group(),
+            None => Err(Error::new(
+                errors::ErrorKind::ExtraEndGroup,
+                r"Extra \endgroup".to_string(),
+            )),
+            Some(kind) => {
+                // Like TeX, read the missing token before the `\endgroup`.
+                self.back_input(Token::ControlSequence(Symbol::intern(r"\endgroup")))?;
+                if kind.closing() == "}" {
+                    self.push_tokens(
+                        TokenListKind::Inserted,
+                        vec![Token::Character('}', CharacterCategory::EndGroup)],
+                    )?;
                 }
+                Err(Error::new(
+                    errors::ErrorKind::MissingGroupEnd,
+                    format!("Missing {} inserted", kind.closing()),
+                ))
             }
-            Ok(Token::ControlSequence(command))
-        } else {
-            Err(Error::eof())
         }
     }
-    pub fn get_element(&mut self) -> Result<Token, Error> {
-        let c = match self.read_character() {
-            Some(c) => c,
-            None => return Err(Error::eof()),
-        };
-
-        match self.get_category(c) {
-            CharacterCategory::Escape => self.get_control(),
-            CharacterCategory::Active => Ok(Token::ControlSequence(c.to_string())),
-            CharacterCategory::Parameter => {
-                let mut s = String::new();
-                let mut n = 0;
-                while let Some(c) = self.read_ahead_character(n) {
-                    if c.is_numeric() {
-                        s.push(c);
-
-                        n += 1;
-                    } else {
-                        break;
+    /// The file, line and column of the next character of the innermost file
+    /// being read.
+    pub fn location(&mut self) -> Option<(String, usize, usize)> {
+        let file = self.input.innermost_file()?;
+        let (line, column) = file.get_text_position();
+        Some((file.name().to_string(), line, column))
+    }
+    /// Shows where the input currently is, like TeX's `show_context`: two
+    /// lines for each token list being read, which together form a traceback
+    /// of the active macros, and two for the current line of the innermost
+    /// file.
+    pub fn show_context(&self) -> Vec<String> {
+        let escape = self.escape_char();
+        let end_line_char = self.state.get_integer(IntegerParameter::EndLineChar);
+        let levels = self.input.levels();
+        let mut lines = Vec::new();
+        for (i, level) in levels.iter().enumerate().rev() {
+            let (before, after) = match level {
+                InputLevel::File(file) => {
+                    let (read, rest) = file.context(end_line_char);
+                    (format!("l.{} {}", file.get_text_position().0, read), rest)
+                }
+                InputLevel::TokenList(list) => {
+                    let exhausted = list.is_exhausted();
+                    // Backed up tokens that have been read again are only
+                    // shown if nothing was read after them.
+                    if exhausted && i + 1 != levels.len() && *list.kind() == TokenListKind::BackedUp
+                    {
+                        continue;
                     }
+                    let label = match list.kind() {
+                        TokenListKind::Macro(name) => {
+                            show_tokens(&[Token::ControlSequence(*name)], escape) + "->"
+                        }
+                        TokenListKind::BackedUp if exhausted => "<recently read> ".to_string(),
+                        TokenListKind::BackedUp | TokenListKind::NoExpand => {
+                            "<to be read again> ".to_string()
+                        }
+                        TokenListKind::Inserted => "<inserted text> ".to_string(),
+                    };
+                    (
+                        label + &show_tokens(list.read(), escape),
+                        show_tokens(list.remaining(), escape),
+                    )
                 }
-                self.advance_by(n);
-                Ok(Token::Parameter(c, s.parse()?))
+            };
+            lines.extend(errors::context_lines(&before, &after));
+            if let InputLevel::File(_) = level {
+                break;
             }
-            CharacterCategory::Comment => {
-                self.skip_comment()?;
-                self.get_element()
-            }
-            a => Ok(Token::Character(c, a)),
         }
+        lines
     }
-    pub fn push_group(&mut self) {
-        self.state = self.state.clone().child();
+    /// The statistics TeX shows at the end of a run about the string pool,
+    /// which here holds the names of control sequences.
+    pub fn memory_usage(&self) -> Vec<String> {
+        let (strings, characters) = parser::symbol::usage();
+        vec![
+            "Here is how much of TeX's memory you used:".to_string(),
+            format!(" {} strings out of {}", strings, constants::MAX_STRINGS),
+            format!(
+                " {} string characters out of {}",
+                characters,
+                constants::POOL_SIZE
+            ),
+        ]
     }
-    pub fn pop_group(&mut self) -> Result<(), Error> {
-        let state = self.state.clone().pop();
-        self.state = state.ok_or(Error::new(
-            errors::ErrorKind::UnknownError,
-            "No states left to pop".to_string(),
-        ))?;
-        Ok(())
+    /// The messages TeX shows when the input ends inside groups or
+    /// conditionals, innermost first.
+    pub fn final_cleanup(&self) -> Vec<String> {
+        let mut messages = Vec::new();
+        if !self.groups.is_empty() {
+            messages.push(format!(
+                r"(\end occurred inside a group at level {})",
+                self.groups.len()
+            ));
+            for (level, group) in self.groups.iter().enumerate().rev() {
+                messages.push(format!(
+                    "### {} (level {}) entered at line {}",
+                    group.kind.description(),
+                    level + 1,
+                    group.line
+                ));
+            }
+        }
+        for condition in self.conditions.iter().rev() {
+            messages.push(format!(
+                r"(\end occurred when {} on line {} was incomplete)",
+                condition.kind.name(),
+                condition.line
+            ));
+        }
+        messages
     }
+    /// Reads and carries out commands until the input ends. Errors are
+    /// handled according to the interaction mode; an error is only returned
+    /// if the job has to end because of it.
     pub fn parse_and_execute(&mut self) -> Result<(), Error> {
         loop {
-            self.parse_and_execute_one()?;
+            match self.parse_and_execute_one() {
+                Ok(()) => {}
+                Err(e) if matches!(e.kind(), errors::ErrorKind::EndOfFile) => return Ok(()),
+                Err(e) if e.is_fatal() => return Err(self.fatal_error(e)),
+                Err(e) => self.error(e)?,
+            }
         }
-        // Ok(())
     }
+    /// Reads and carries out the next command. Errors are given the location
+    /// and context of the input at the point where they happened.
     pub fn parse_and_execute_one(&mut self) -> Result<(), Error> {
-        let token = self.get_element()?;
-        self.execute_token(token.clone())?;
-        Ok(())
+        self.execute_next().map_err(|mut e| {
+            e.locate(self.location(), self.show_context());
+            e
+        })
+    }
+    fn execute_next(&mut self) -> Result<(), Error> {
+        let (token, expand) = self.get_next()?;
+        if !expand {
+            if let Token::ControlSequence(name) = &token {
+                if self
+                    .state
+                    .get_macro(*name)
+                    .is_some_and(|m| m.is_expandable())
+                {
+                    // An expandable command after `\noexpand` acts as `\relax`.
+                    return Ok(());
+                }
+            }
+        }
+        // Only the end of the input between commands ends the job; in the
+        // middle of a command it is an error.
+        let name = token.to_string();
+        self.execute_token(token).map_err(|e| match e.kind() {
+            errors::ErrorKind::EndOfFile => Error::new(
+                errors::ErrorKind::Runaway,
+                format!("File ended while scanning use of {}", name),
+            ),
+            _ => e,
+        })
     }
     pub fn execute_token(&mut self, token: Token) -> Result<(), Error> {
-        match token.clone() {
-            Token::ControlSequence(s) => self.state.clone().run_macro(&s, self)?,
-            Token::Character(_, CharacterCategory::BeginGroup) => self.push_group(),
-            Token::Character(_, CharacterCategory::EndGroup) => self.pop_group()?,
-            Token::Character(chr, cat) => println!("{chr:?} {cat:?}"),
-            Token::Parameter(_, _) => {
+        match token {
+            Token::ControlSequence(s) => match self.state.get_macro(s) {
+                Some(m) => m.clone().run(self)?,
+                None => {
+                    return Err(Error::new(
+                        errors::ErrorKind::UndefinedControlSequence,
+                        "Undefined control sequence".to_string(),
+                    ))
+                }
+            },
+            Token::Character(_, CharacterCategory::BeginGroup) => {
+                self.push_group(GroupKind::Simple)
+            }
+            Token::Character(_, CharacterCategory::EndGroup) => self.handle_right_brace()?,
+            // There is nothing to typeset characters into yet.
+            Token::Character(..) => {}
+            Token::Parameter(c, n) => {
                 return Err(Error::new(
-                    errors::ErrorKind::UnknownError,
-                    "Parameter in text".to_string(),
+                    errors::ErrorKind::CantUseInMode,
+                    format!(
+                        "You can't use `macro parameter {}{}' in {}",
+                        c,
+                        n,
+                        self.mode.name()
+                    ),
                 ))
             }
         };
         Ok(())
     }
-    pub fn define(&mut self, d: Box<dyn Macro>) {
-        self.state.set_macro(d);
+    /// The character printed before the names of control sequences, or
+    /// `None` if `\escapechar` is not a valid character.
+    pub fn escape_char(&self) -> Option<char> {
+        u32::try_from(self.state.get_integer(IntegerParameter::EscapeChar))
+            .ok()
+            .and_then(char::from_u32)
     }
-
-    fn skip_comment(&mut self) -> Result<(), Error> {
-        while let Some(e) = self.read_character() {
-            if self.get_category(e) == CharacterCategory::EndOfLine {
-                return Ok(());
-            }
+    /// Whether the assignment being executed is global, taking `\global` and
+    /// `\globaldefs` into account. The `\global` prefix is used up by this.
+    pub fn global_assignment(&mut self) -> bool {
+        let prefixed = std::mem::take(&mut self.prefixes.global);
+        match self.state.get_integer(IntegerParameter::GlobalDefs) {
+            n if n > 0 => true,
+            n if n < 0 => false,
+            _ => prefixed,
         }
-        Err(Error::eof())
+    }
+    /// Gives the control sequence `name` the meaning `d`, or makes it
+    /// undefined if that is `None`, taking the prefixes into account.
+    pub fn define(&mut self, name: Symbol, d: Option<Command>) {
+        let global = self.global_assignment();
+        self.state.set_macro_with_global(name, d, global);
     }
 }
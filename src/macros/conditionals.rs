@@ -0,0 +1,415 @@
+use crate::boxes::BoxKind;
+use crate::errors::ErrorKind;
+use crate::parser::input_stack::TokenListKind;
+use crate::parser::{lexer::CharacterCategory, parser::Token};
+
+use super::primitives::Relax;
+use super::*;
+
+/// The parts of a conditional, ordered as TeX's `if_code`, `fi_code`,
+/// `else_code` and `or_code`. Besides naming `\fi`, `\else` and `\or`, these
+/// are the limits of a conditional: the part that may come next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConditionCode {
+    If,
+    Fi,
+    Else,
+    Or,
+}
+
+impl ConditionCode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConditionCode::If => r"\if",
+            ConditionCode::Fi => r"\fi",
+            ConditionCode::Else => r"\else",
+            ConditionCode::Or => r"\or",
+        }
+    }
+}
+
+/// The conditional primitives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfKind {
+    Char,
+    Cat,
+    Num,
+    Dim,
+    Odd,
+    VMode,
+    HMode,
+    MMode,
+    Inner,
+    Void,
+    HBox,
+    VBox,
+    X,
+    True,
+    False,
+    Case,
+}
+
+impl IfKind {
+    pub const ALL: [IfKind; 16] = [
+        IfKind::Char,
+        IfKind::Cat,
+        IfKind::Num,
+        IfKind::Dim,
+        IfKind::Odd,
+        IfKind::VMode,
+        IfKind::HMode,
+        IfKind::MMode,
+        IfKind::Inner,
+        IfKind::Void,
+        IfKind::HBox,
+        IfKind::VBox,
+        IfKind::X,
+        IfKind::True,
+        IfKind::False,
+        IfKind::Case,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IfKind::Char => r"\if",
+            IfKind::Cat => r"\ifcat",
+            IfKind::Num => r"\ifnum",
+            IfKind::Dim => r"\ifdim",
+            IfKind::Odd => r"\ifodd",
+            IfKind::VMode => r"\ifvmode",
+            IfKind::HMode => r"\ifhmode",
+            IfKind::MMode => r"\ifmmode",
+            IfKind::Inner => r"\ifinner",
+            IfKind::Void => r"\ifvoid",
+            IfKind::HBox => r"\ifhbox",
+            IfKind::VBox => r"\ifvbox",
+            IfKind::X => r"\ifx",
+            IfKind::True => r"\iftrue",
+            IfKind::False => r"\iffalse",
+            IfKind::Case => r"\ifcase",
+        }
+    }
+}
+
+/// An entry of the condition stack.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub kind: IfKind,
+    pub limit: ConditionCode,
+    /// The line on which the conditional started.
+    pub line: usize,
+}
+
+/// Skips tokens up to the next `\fi`, `\else` or `\or` that is not part of a
+/// nested conditional, returning which of them was found.
+fn pass_text(state: &mut TexState, kind: IfKind, line: usize) -> Result<ConditionCode, Error> {
+    let mut level = 0usize;
+    loop {
+        let t = state.get_element().map_err(|e| match e.kind() {
+            ErrorKind::EndOfFile => Error::new(
+                ErrorKind::IncompleteConditional,
+                format!(
+                    "Incomplete {}; all text was ignored after line {}",
+                    kind.name(),
+                    line
+                ),
+            ),
+            _ => e,
+        })?;
+        if is_outer(state, &t) {
+            return Err(Error::new(
+                ErrorKind::IncompleteConditional,
+                format!(
+                    "Incomplete {}; all text was ignored after line {}",
+                    kind.name(),
+                    line
+                ),
+            ));
+        }
+        let Token::ControlSequence(name) = t else {
+            continue;
+        };
+        match state.state.get_macro(name).and_then(|m| m.conditional()) {
+            Some(ConditionCode::If) => level += 1,
+            Some(code) if level == 0 => return Ok(code),
+            Some(ConditionCode::Fi) => level -= 1,
+            _ => {}
+        }
+    }
+}
+
+/// Finishes a conditional whose untaken branch has been skipped up to the
+/// `\fi`, `\else` or `\or` given by `code`.
+fn finish_skipping(state: &mut TexState, code: ConditionCode) {
+    if code == ConditionCode::Fi {
+        state.conditions.pop();
+    } else if let Some(c) = state.conditions.last_mut() {
+        c.limit = ConditionCode::Fi;
+    }
+}
+
+/// The category and character code compared by `\if` and `\ifcat`. Implicit
+/// characters such as `\bgroup` compare as their character, and other
+/// control sequences that are not active characters compare as TeX's `\relax`.
+fn char_and_category(state: &TexState, t: &Token) -> (Option<CharacterCategory>, Option<char>) {
+    match t {
+        Token::Character(c, cat) => (Some(*cat), Some(*c)),
+        Token::Parameter(c, _) => (Some(CharacterCategory::Parameter), Some(*c)),
+        Token::ControlSequence(name) => match state.state.get_macro(*name) {
+            Some(Command::Character(c, cat)) => (Some(*cat), Some(*c)),
+            _ if name.is_active() => (Some(CharacterCategory::Active), name.name().chars().next()),
+            _ => (None, None),
+        },
+    }
+}
+
+/// What `\ifx` compares: the category and character code of a character,
+/// or the meaning of a control sequence (`None` if it is undefined).
+enum Meaning {
+    Character(Option<CharacterCategory>, Option<char>),
+    Command(Option<Command>),
+}
+
+impl PartialEq for Meaning {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Meaning::Character(a, b), Meaning::Character(c, d)) => a == c && b == d,
+            (Meaning::Command(Some(a)), Meaning::Command(Some(b))) => a.same_meaning(b),
+            (Meaning::Command(None), Meaning::Command(None)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Reads the next unexpanded token for `\ifx`.
+fn next_meaning(state: &mut TexState) -> Result<Meaning, Error> {
+    let (t, expand) = state.get_next()?;
+    Ok(match &t {
+        Token::ControlSequence(name) => match state.state.get_macro(*name) {
+            // After `\noexpand` an expandable command means `\relax`.
+            Some(m) if m.is_expandable() && !expand => {
+                Meaning::Command(Some(Command::Unexpandable(Box::new(Relax))))
+            }
+            Some(Command::Character(c, cat)) => Meaning::Character(Some(*cat), Some(*c)),
+            m => Meaning::Command(m.cloned()),
+        },
+        t => {
+            let (cat, c) = char_and_category(state, t);
+            Meaning::Character(cat, c)
+        }
+    })
+}
+
+/// Scans the relation of `\ifnum` or `\ifdim`.
+fn scan_relation(state: &mut TexState, kind: IfKind) -> Result<char, Error> {
+    match state.get_x_non_blank()? {
+        Token::Character(c @ ('<' | '=' | '>'), CharacterCategory::Other) => Ok(c),
+        t => {
+            state.back_input(t)?;
+            Err(Error::new(
+                ErrorKind::MissingRelation,
+                format!("Missing = inserted for {}", kind.name()),
+            ))
+        }
+    }
+}
+
+fn compare<T: Ord>(a: T, relation: char, b: T) -> bool {
+    match relation {
+        '<' => a < b,
+        '>' => a > b,
+        _ => a == b,
+    }
+}
+
+/// Evaluates the condition of every conditional except `\ifcase`.
+fn evaluate(state: &mut TexState, kind: IfKind) -> Result<bool, Error> {
+    Ok(match kind {
+        IfKind::Char | IfKind::Cat => {
+            let a = state.get_x_token()?;
+            let a = char_and_category(state, &a);
+            let b = state.get_x_token()?;
+            let b = char_and_category(state, &b);
+            if kind == IfKind::Char {
+                a.1 == b.1
+            } else {
+                a.0 == b.0
+            }
+        }
+        IfKind::Num => {
+            let a = state.scan_int()?;
+            let relation = scan_relation(state, kind)?;
+            compare(a, relation, state.scan_int()?)
+        }
+        IfKind::Dim => {
+            let a = state.scan_dimen()?;
+            let relation = scan_relation(state, kind)?;
+            compare(a, relation, state.scan_dimen()?)
+        }
+        IfKind::Odd => state.scan_int()? % 2 != 0,
+        IfKind::VMode => state.mode.is_vertical(),
+        IfKind::HMode => state.mode.is_horizontal(),
+        IfKind::MMode => state.mode.is_math(),
+        IfKind::Inner => state.mode.is_inner(),
+        IfKind::Void | IfKind::HBox | IfKind::VBox => {
+            let n = state.scan_register_num()?;
+            match (kind, state.state.get_box(n)) {
+                (IfKind::Void, b) => b.is_none(),
+                (IfKind::HBox, Some(b)) => b.kind == BoxKind::Horizontal,
+                (IfKind::VBox, Some(b)) => b.kind == BoxKind::Vertical,
+                _ => false,
+            }
+        }
+        IfKind::X => {
+            let a = next_meaning(state)?;
+            a == next_meaning(state)?
+        }
+        IfKind::True => true,
+        IfKind::False => false,
+        IfKind::Case => unreachable!(),
+    })
+}
+
+/// Evaluates the test of the conditional at `depth` on the condition stack
+/// and skips to the branch that is taken. As in TeX, an error in the test is
+/// reported and the test is taken to be false, or 0 for `\ifcase`.
+fn choose_branch(
+    state: &mut TexState,
+    kind: IfKind,
+    line: usize,
+    depth: usize,
+) -> Result<(), Error> {
+    if kind == IfKind::Case {
+        let mut n = match state.scan_int() {
+            Ok(n) => n,
+            Err(e) => {
+                state.recoverable_error(e)?;
+                0
+            }
+        };
+        while n != 0 {
+            let code = pass_text(state, kind, line)?;
+            if state.conditions.len() == depth {
+                if code != ConditionCode::Or {
+                    finish_skipping(state, code);
+                    return Ok(());
+                }
+                n -= 1;
+            } else if code == ConditionCode::Fi {
+                state.conditions.pop();
+            }
+        }
+        state.conditions[depth - 1].limit = ConditionCode::Or;
+        return Ok(());
+    }
+    let value = match evaluate(state, kind) {
+        Ok(value) => value,
+        Err(e) => {
+            state.recoverable_error(e)?;
+            false
+        }
+    };
+    if value {
+        state.conditions[depth - 1].limit = ConditionCode::Else;
+        return Ok(());
+    }
+    loop {
+        let code = pass_text(state, kind, line)?;
+        if state.conditions.len() == depth {
+            if code != ConditionCode::Or {
+                finish_skipping(state, code);
+                return Ok(());
+            }
+            // TeX complains and goes on skipping.
+            state.recoverable_error(Error::new(
+                ErrorKind::ExtraConditional,
+                r"Extra \or".to_string(),
+            ))?;
+        } else if code == ConditionCode::Fi {
+            state.conditions.pop();
+        }
+    }
+}
+
+/// `\ifnum`, `\ifx` and the other conditionals.
+#[derive(Clone, Debug)]
+pub struct IfTest(pub IfKind);
+
+impl Macro for IfTest {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Expandable for IfTest {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let kind = self.0;
+        let line = state.input.line();
+        state.conditions.push(Condition {
+            kind,
+            limit: ConditionCode::If,
+            line,
+        });
+        let depth = state.conditions.len();
+        let result = choose_branch(state, kind, line, depth);
+        if result.is_err() {
+            // The conditional cannot be finished, so it must not be left on
+            // the stack with its test still being evaluated.
+            state.conditions.truncate(depth - 1);
+        }
+        result
+    }
+
+    fn conditional(&self) -> Option<ConditionCode> {
+        Some(ConditionCode::If)
+    }
+}
+
+/// `\fi`, `\else` and `\or`, which end the current branch of a conditional.
+#[derive(Clone, Debug)]
+pub struct FiOrElse(pub ConditionCode);
+
+impl Macro for FiOrElse {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Expandable for FiOrElse {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let Some(condition) = state.conditions.last() else {
+            return Err(Error::new(
+                ErrorKind::ExtraConditional,
+                format!("Extra {}", self.0.name()),
+            ));
+        };
+        if self.0 <= condition.limit {
+            let (kind, line) = (condition.kind, state.input.line());
+            let mut code = self.0;
+            while code != ConditionCode::Fi {
+                code = pass_text(state, kind, line)?;
+            }
+            state.conditions.pop();
+            Ok(())
+        } else if condition.limit == ConditionCode::If {
+            // The condition is still being evaluated, so it is ended by an
+            // inserted `\relax` before this token is seen again.
+            state.push_tokens(
+                TokenListKind::Inserted,
+                vec![
+                    Token::ControlSequence(Symbol::intern(r"\relax")),
+                    Token::ControlSequence(Symbol::intern(self.0.name())),
+                ],
+            )
+        } else {
+            Err(Error::new(
+                ErrorKind::ExtraConditional,
+                format!("Extra {}", self.0.name()),
+            ))
+        }
+    }
+
+    fn conditional(&self) -> Option<ConditionCode> {
+        Some(self.0)
+    }
+}
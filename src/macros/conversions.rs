@@ -0,0 +1,134 @@
+use crate::errors::ErrorKind;
+use crate::parser::input_stack::TokenListKind;
+use crate::parser::{lexer::CharacterCategory, parser::Token};
+use crate::scanning::str_toks;
+
+use super::primitives::Relax;
+use super::*;
+
+/// `\csname`, which makes a control sequence from the characters up to the
+/// matching `\endcsname`.
+#[derive(Clone, Debug)]
+pub struct CsName;
+
+impl Macro for CsName {
+    fn name(&self) -> String {
+        r"\csname".to_string()
+    }
+}
+
+impl Expandable for CsName {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let mut name = String::from('\\');
+        loop {
+            match state.get_x_token()? {
+                Token::Character(c, _) => name.push(c),
+                Token::ControlSequence(cs)
+                    if state
+                        .state
+                        .get_macro(cs)
+                        .is_some_and(|m| m.meaning() == r"\endcsname") =>
+                {
+                    break
+                }
+                t => {
+                    state.back_input(t)?;
+                    return Err(Error::new(
+                        ErrorKind::MissingEndCsName,
+                        r"Missing \endcsname inserted".to_string(),
+                    ));
+                }
+            }
+        }
+        // An undefined name means `\relax` in the current group.
+        let name = Symbol::intern(&name);
+        if state.state.get_macro(name).is_none() {
+            state.state.set_macro_with_global(
+                name,
+                Some(Command::Unexpandable(Box::new(Relax))),
+                false,
+            );
+        }
+        state.back_input(Token::ControlSequence(name))
+    }
+}
+
+/// `\endcsname`, which is only allowed at the end of `\csname`.
+#[derive(Clone, Debug)]
+pub struct EndCsName;
+
+impl Macro for EndCsName {
+    fn name(&self) -> String {
+        r"\endcsname".to_string()
+    }
+}
+
+impl Unexpandable for EndCsName {
+    fn execute(&self, _: &mut TexState) -> Result<(), Error> {
+        Err(Error::new(
+            ErrorKind::ExtraEndCsName,
+            r"Extra \endcsname".to_string(),
+        ))
+    }
+}
+
+/// `\string`, which turns the next token into characters.
+#[derive(Clone, Debug)]
+pub struct StringOf;
+
+impl Macro for StringOf {
+    fn name(&self) -> String {
+        r"\string".to_string()
+    }
+}
+
+impl Expandable for StringOf {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let text = match state.get_element()? {
+            Token::ControlSequence(name) => escaped_symbol(name, state.escape_char()),
+            t => t.to_string(),
+        };
+        state.push_tokens(TokenListKind::Inserted, str_toks(&text))
+    }
+}
+
+/// `\meaning`, which turns the meaning of the next token into characters.
+#[derive(Clone, Debug)]
+pub struct MeaningOf;
+
+impl Macro for MeaningOf {
+    fn name(&self) -> String {
+        r"\meaning".to_string()
+    }
+}
+
+impl Expandable for MeaningOf {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let text = match state.get_element()? {
+            Token::ControlSequence(name) => match state.state.get_macro(name) {
+                Some(m) => m.print_meaning(state.escape_char()),
+                None => "undefined".to_string(),
+            },
+            Token::Character(c, cat) => describe_character(c, cat),
+            Token::Parameter(c, _) => describe_character(c, CharacterCategory::Parameter),
+        };
+        state.push_tokens(TokenListKind::Inserted, str_toks(&text))
+    }
+}
+
+/// `\the`, which turns the value of an internal quantity into tokens.
+#[derive(Clone, Debug)]
+pub struct The;
+
+impl Macro for The {
+    fn name(&self) -> String {
+        r"\the".to_string()
+    }
+}
+
+impl Expandable for The {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let tokens = state.the_toks()?;
+        state.push_tokens(TokenListKind::Inserted, tokens)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::errors::ErrorKind;
+use crate::scanning::Value;
+
+use super::*;
+
+/// `\input`, which starts reading a file, given either as a name ended by a
+/// space or as `{name}`.
+#[derive(Clone, Debug)]
+pub struct InputFile;
+
+impl Macro for InputFile {
+    fn name(&self) -> String {
+        r"\input".to_string()
+    }
+}
+
+impl Expandable for InputFile {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = state.scan_file_name()?;
+        let file = state.open_input_file(&name)?;
+        state.input.push_file(file)
+    }
+}
+
+/// `\endinput`, which ends the current file after its current line.
+#[derive(Clone, Debug)]
+pub struct EndInput;
+
+impl Macro for EndInput {
+    fn name(&self) -> String {
+        r"\endinput".to_string()
+    }
+}
+
+impl Expandable for EndInput {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        if let Some(file) = state.input.innermost_file() {
+            file.end_input();
+        }
+        Ok(())
+    }
+}
+
+/// `\inputlineno`, the number of the current line of the innermost file.
+#[derive(Clone, Debug)]
+pub struct InputLineNo;
+
+impl Macro for InputLineNo {
+    fn name(&self) -> String {
+        r"\inputlineno".to_string()
+    }
+}
+
+impl Unexpandable for InputLineNo {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        Err(Error::new(
+            ErrorKind::CantUseInMode,
+            format!(r"You can't use `\inputlineno' in {}", state.mode.name()),
+        ))
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::Integer(state.input.line() as i32)))
+    }
+}
@@ -0,0 +1,182 @@
+use crate::boxes::BoxKind;
+use crate::errors::ErrorKind;
+use crate::parameters::TokenParameter;
+use crate::scanning::arithmetic::format_scaled;
+
+use super::*;
+
+/// Reads the expanded text in braces after `\message` or `\errmessage`, the
+/// command `name`, and returns it as TeX prints it.
+fn scan_message(state: &mut TexState, name: &str) -> Result<String, Error> {
+    let tokens = state.scan_toks(true).map_err(|e| match e.kind() {
+        ErrorKind::EndOfFile => Error::new(
+            ErrorKind::Runaway,
+            format!("File ended while scanning text of {}", name),
+        ),
+        _ => e,
+    })?;
+    Ok(show_tokens(&tokens, state.escape_char()))
+}
+
+/// `\message`, which shows a text on the terminal and in the transcript.
+#[derive(Clone, Debug)]
+pub struct Message;
+
+impl Macro for Message {
+    fn name(&self) -> String {
+        r"\message".to_string()
+    }
+}
+
+impl Unexpandable for Message {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let text = scan_message(state, r"\message")?;
+        state.print_message(&text);
+        Ok(())
+    }
+}
+
+/// `\errmessage`, which reports an error with a text given by the document
+/// and the help given by `\errhelp`.
+#[derive(Clone, Debug)]
+pub struct ErrMessage;
+
+impl Macro for ErrMessage {
+    fn name(&self) -> String {
+        r"\errmessage".to_string()
+    }
+}
+
+impl Unexpandable for ErrMessage {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let text = scan_message(state, r"\errmessage")?;
+        let error = Error::new(ErrorKind::UserError, text);
+        let help = state.state.get_tokens(TokenParameter::ErrHelp);
+        if !help.is_empty() {
+            let help = show_tokens(&help, state.escape_char());
+            return Err(error.with_help(&[&help]));
+        }
+        // Like TeX, the long help is only given once when not stopping.
+        if state.long_help_seen {
+            return Err(error.with_help(&["(That was another \\errmessage.)"]));
+        }
+        if state.interaction < InteractionMode::ErrorStop {
+            state.long_help_seen = true;
+        }
+        Err(error)
+    }
+}
+
+/// The commands that show something and then stop as for an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShowKind {
+    Show,
+    ShowBox,
+    ShowThe,
+    ShowLists,
+}
+
+impl ShowKind {
+    pub const ALL: [ShowKind; 4] = [
+        ShowKind::Show,
+        ShowKind::ShowBox,
+        ShowKind::ShowThe,
+        ShowKind::ShowLists,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShowKind::Show => r"\show",
+            ShowKind::ShowBox => r"\showbox",
+            ShowKind::ShowThe => r"\showthe",
+            ShowKind::ShowLists => r"\showlists",
+        }
+    }
+}
+
+/// `\show`, `\showbox`, `\showthe` and `\showlists`. The meaning of a token
+/// and the value of a quantity are shown where errors are; boxes and lists
+/// are diagnostics, which only go to the transcript unless `\tracingonline`
+/// is positive.
+#[derive(Clone, Debug)]
+pub struct Show(pub ShowKind);
+
+impl Macro for Show {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for Show {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let escape = state.escape_char();
+        let shown = match self.0 {
+            ShowKind::Show => {
+                let t = state.get_element()?;
+                let meaning = match t {
+                    Token::ControlSequence(name) => {
+                        let meaning = match state.state.get_macro(name) {
+                            // The replacement text of a macro starts a line.
+                            Some(Command::Expandable(m)) if m.as_macro().is_some() => {
+                                m.print_meaning(escape).replacen("macro:", "macro:\n", 1)
+                            }
+                            Some(m) => m.print_meaning(escape),
+                            None => "undefined".to_string(),
+                        };
+                        format!("{}={}", escaped_symbol(name, escape), meaning)
+                    }
+                    Token::Character(c, cat) => describe_character(c, cat),
+                    Token::Parameter(c, _) => describe_character(c, CharacterCategory::Parameter),
+                };
+                format!("> {}", meaning)
+            }
+            ShowKind::ShowThe => {
+                let tokens = state.the_toks()?;
+                format!("> {}", show_tokens(&tokens, escape))
+            }
+            ShowKind::ShowBox => {
+                let n = state.scan_register_num()?;
+                let shown = match state.state.get_box(n) {
+                    None => "void".to_string(),
+                    Some(b) => format!(
+                        "\\{}box({}+{})x{}",
+                        match b.kind {
+                            BoxKind::Horizontal => 'h',
+                            BoxKind::Vertical => 'v',
+                        },
+                        format_scaled(b.height),
+                        format_scaled(b.depth),
+                        format_scaled(b.width)
+                    ),
+                };
+                show_diagnostic(state, &[format!("> \\box{}={}", n, shown)])
+            }
+            ShowKind::ShowLists => {
+                // There are no lists being built yet, so only the current
+                // mode is known.
+                show_diagnostic(state, &[format!("### {}", state.mode.name())])
+            }
+        };
+        let error = Error::new(ErrorKind::Shown, shown);
+        Err(if state.interaction < InteractionMode::ErrorStop {
+            error.with_help(&[])
+        } else if state.state.get_integer(IntegerParameter::TracingOnline) > 0 {
+            error.with_help(&ErrorKind::Shown.help()[..3])
+        } else {
+            error
+        })
+    }
+}
+
+/// Prints `lines` as a diagnostic and returns the message that follows it.
+fn show_diagnostic(state: &mut TexState, lines: &[String]) -> String {
+    state.begin_diagnostic();
+    for line in lines {
+        state.print_nl(line);
+    }
+    state.end_diagnostic(true);
+    SHOWN_OK.to_string()
+}
+
+/// The message after a box or list has been shown.
+pub(crate) const SHOWN_OK: &str = "! OK";
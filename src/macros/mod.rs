@@ -2,79 +2,460 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 
 use crate::errors::Error;
-use crate::parser::parser::Token;
-use crate::TexState;
+use crate::interaction::InteractionMode;
+use crate::parameters::{IntegerParameter, RegisterKind, TokenParameter};
+use crate::parser::{
+    input_stack::TokenListKind, lexer::CharacterCategory, parser::Token, symbol::Symbol,
+};
+use crate::scanning::Value;
+use crate::{Prefixes, TexState};
+use conditionals::{ConditionCode, FiOrElse, IfKind, IfTest};
+use conversions::{CsName, EndCsName, MeaningOf, StringOf, The};
 use dyn_clone::DynClone;
+use files::{EndInput, InputFile, InputLineNo};
+use messages::{ErrMessage, Message, Show, ShowKind};
 
+pub mod conditionals;
+pub mod conversions;
+pub mod files;
+pub mod messages;
 mod pattern_matcher;
 use pattern_matcher::*;
 pub mod primitives;
+/// What all commands have in common.
 pub trait Macro: DynClone + Debug {
     fn name<'a>(&self) -> String;
-    fn run(&self, state: &mut TexState) -> Result<(), Error>;
-    fn safe(&self, state: &TexState) -> bool;
+    /// What `\meaning` shows for the command; `\ifx` compares these.
+    fn meaning(&self) -> String {
+        self.name()
+    }
+    /// The meaning as `\meaning` prints it, with `escape` in place of the
+    /// backslash before the names of control sequences.
+    fn print_meaning(&self, escape: Option<char>) -> String {
+        escaped(&self.meaning(), escape)
+    }
+}
+
+/// A command that is replaced by other tokens when it is expanded, such as a
+/// macro or a conditional. These are expanded whenever expanded tokens are
+/// read.
+pub trait Expandable: Macro {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error>;
+    /// Which part of a conditional the command is, if any. Used to find the
+    /// end of the text skipped by a conditional.
+    fn conditional(&self) -> Option<ConditionCode> {
+        None
+    }
+    /// Whether the command is left unexpanded when a token list is fully
+    /// expanded, as for the body of `\edef`.
+    fn protected(&self) -> bool {
+        false
+    }
+    /// Whether the command is an `\outer` macro, which may not appear in
+    /// arguments, definitions and skipped conditional text.
+    fn outer(&self) -> bool {
+        false
+    }
+    /// The macro itself, if the command was defined by `\def` or one of its
+    /// relatives.
+    fn as_macro(&self) -> Option<&UserDefinedMacro> {
+        None
+    }
+}
+
+/// A command that is carried out by the main loop.
+pub trait Unexpandable: Macro {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error>;
+    /// The value of the command when used as an internal quantity, e.g. after
+    /// `\the` or in place of a number. Returns `None` for commands that are
+    /// not internal quantities.
+    fn value(&self, _: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(None)
+    }
+    /// Which prefixes the command accepts.
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::None
+    }
+}
+dyn_clone::clone_trait_object!(Expandable);
+dyn_clone::clone_trait_object!(Unexpandable);
+
+/// How an unexpandable command combines with the prefixes `\global`,
+/// `\long`, `\outer` and `\protected`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefixing {
+    /// The command takes no prefixes.
+    None,
+    /// The command is itself a prefix.
+    Prefix,
+    /// The command is an assignment, which may be made `\global`.
+    Assignment,
+    /// The command defines a macro and takes all prefixes.
+    Definition,
+}
+
+/// The meaning of a control sequence. Like TeX's command codes, this tells
+/// expandable commands from those that are left to the main loop.
+#[derive(Clone, Debug)]
+pub enum Command {
+    Expandable(Box<dyn Expandable>),
+    Unexpandable(Box<dyn Unexpandable>),
+    /// An implicit character such as `\bgroup`, made by `\let`.
+    Character(char, CharacterCategory),
+}
+
+impl Command {
+    pub fn name(&self) -> String {
+        match self {
+            Command::Expandable(m) => m.name(),
+            Command::Unexpandable(m) => m.name(),
+            Command::Character(c, cat) => describe_character(*c, *cat),
+        }
+    }
+    pub fn meaning(&self) -> String {
+        match self {
+            Command::Expandable(m) => m.meaning(),
+            Command::Unexpandable(m) => m.meaning(),
+            Command::Character(c, cat) => describe_character(*c, *cat),
+        }
+    }
+    /// The meaning as `\meaning` prints it, with `escape` as the escape
+    /// character.
+    pub fn print_meaning(&self, escape: Option<char>) -> String {
+        match self {
+            Command::Expandable(m) => m.print_meaning(escape),
+            Command::Unexpandable(m) => m.print_meaning(escape),
+            Command::Character(c, cat) => describe_character(*c, *cat),
+        }
+    }
+    /// Whether the two commands have the same meaning, as `\ifx` decides it:
+    /// macros have to have the same prefixes, parameter text and replacement
+    /// text, and all other commands have to be the same command.
+    pub fn same_meaning(&self, other: &Command) -> bool {
+        match (self, other) {
+            (Command::Expandable(a), Command::Expandable(b)) => {
+                match (a.as_macro(), b.as_macro()) {
+                    (Some(a), Some(b)) => a == b,
+                    (None, None) => a.name() == b.name(),
+                    _ => false,
+                }
+            }
+            (Command::Unexpandable(a), Command::Unexpandable(b)) => a.meaning() == b.meaning(),
+            (Command::Character(c, cat), Command::Character(d, dat)) => c == d && cat == dat,
+            _ => false,
+        }
+    }
+    pub fn is_expandable(&self) -> bool {
+        matches!(self, Command::Expandable(_))
+    }
+    pub fn is_outer(&self) -> bool {
+        matches!(self, Command::Expandable(m) if m.outer())
+    }
+    /// Which part of a conditional the command is, if any.
+    pub fn conditional(&self) -> Option<ConditionCode> {
+        match self {
+            Command::Expandable(m) => m.conditional(),
+            _ => None,
+        }
+    }
+    /// Expands or executes the command.
+    pub fn run(&self, state: &mut TexState) -> Result<(), Error> {
+        match self {
+            Command::Expandable(m) => m.expand(state),
+            Command::Unexpandable(m) => {
+                m.execute(state)?;
+                if matches!(m.prefixing(), Prefixing::Assignment | Prefixing::Definition) {
+                    if let Some(t) = state.after_assignment.take() {
+                        state.back_input(t)?;
+                    }
+                }
+                Ok(())
+            }
+            Command::Character(c, cat) => state.execute_token(Token::Character(*c, *cat)),
+        }
+    }
 }
-dyn_clone::clone_trait_object!(Macro);
 
 #[derive(Clone, Debug)]
 pub struct UserDefinedMacro {
-    name: String,
+    name: Symbol,
     parameters: Vec<Token>,
     replacements: Vec<Token>,
     parameter_count: u8,
+    long: bool,
+    outer: bool,
+    protected: bool,
 }
 impl UserDefinedMacro {
+    /// A macro defined with the `\long`, `\outer` and `\protected` flags of
+    /// `prefixes`.
     fn new(
-        name: String,
+        name: Symbol,
         parameters: Vec<Token>,
         replacements: Vec<Token>,
         parameter_count: u8,
+        prefixes: Prefixes,
     ) -> UserDefinedMacro {
         Self {
             name,
             parameters,
             replacements,
             parameter_count,
+            long: prefixes.long,
+            outer: prefixes.outer,
+            protected: prefixes.protected,
         }
     }
 }
+impl PartialEq for UserDefinedMacro {
+    /// Macros are equal when they mean the same, whatever their names.
+    fn eq(&self, other: &Self) -> bool {
+        self.parameters == other.parameters
+            && self.replacements == other.replacements
+            && self.long == other.long
+            && self.outer == other.outer
+            && self.protected == other.protected
+    }
+}
 impl Macro for UserDefinedMacro {
     fn name(&self) -> String {
-        self.name.clone()
+        self.name.to_string()
+    }
+
+    fn meaning(&self) -> String {
+        self.print_meaning(Some('\\'))
+    }
+
+    fn print_meaning(&self, escape: Option<char>) -> String {
+        let mut meaning = String::new();
+        for (set, prefix) in [
+            (self.protected, r"\protected"),
+            (self.long, r"\long"),
+            (self.outer, r"\outer"),
+        ] {
+            if set {
+                meaning.push_str(&escaped(prefix, escape));
+            }
+        }
+        if !meaning.is_empty() {
+            meaning.push(' ');
+        }
+        format!(
+            "{}macro:{}->{}",
+            meaning,
+            show_tokens(&self.parameters, escape),
+            show_tokens(&self.replacements, escape)
+        )
+    }
+}
+impl Expandable for UserDefinedMacro {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let tracing = state.state.get_integer(IntegerParameter::TracingMacros) > 0;
+        if tracing {
+            let escape = state.escape_char();
+            state.begin_diagnostic();
+            state.print_ln();
+            state.print(&format!(
+                "{}{}->{}",
+                show_tokens(&[Token::ControlSequence(self.name)], escape),
+                show_tokens(&self.parameters, escape),
+                show_tokens(&self.replacements, escape)
+            ));
+            state.end_diagnostic(false);
+        }
+        let arguments = match_pattern(self, state)?;
+        debug_assert_eq!(arguments.len(), self.parameter_count as usize);
+        if tracing && !arguments.is_empty() {
+            let escape = state.escape_char();
+            state.begin_diagnostic();
+            for (i, argument) in arguments.iter().enumerate() {
+                state.print_nl(&format!("#{}<-{}", i + 1, show_tokens(argument, escape)));
+            }
+            state.end_diagnostic(false);
+        }
+        state.push_tokens(
+            TokenListKind::Macro(self.name),
+            substitute(&self.replacements, &arguments),
+        )
+    }
+
+    fn protected(&self) -> bool {
+        self.protected
+    }
+
+    fn outer(&self) -> bool {
+        self.outer
+    }
+
+    fn as_macro(&self) -> Option<&UserDefinedMacro> {
+        Some(self)
+    }
+}
+
+/// Describes a character token the way TeX's `\meaning` does, e.g. `the
+/// letter A` or `begin-group character {`.
+pub fn describe_character(c: char, cat: CharacterCategory) -> String {
+    let description = match cat {
+        CharacterCategory::BeginGroup => "begin-group character",
+        CharacterCategory::EndGroup => "end-group character",
+        CharacterCategory::MathShift => "math shift character",
+        CharacterCategory::AlignmentTab => "alignment tab character",
+        CharacterCategory::Parameter => "macro parameter character",
+        CharacterCategory::Superscript => "superscript character",
+        CharacterCategory::Subscript => "subscript character",
+        CharacterCategory::Space => "blank space",
+        CharacterCategory::Letter => "the letter",
+        _ => "the character",
+    };
+    format!("{} {}", description, c)
+}
+
+/// Whether `t` is a control sequence that means an `\outer` macro.
+pub(crate) fn is_outer(state: &TexState, t: &Token) -> bool {
+    match t {
+        Token::ControlSequence(name) => state.state.get_macro(*name).is_some_and(|m| m.is_outer()),
+        _ => false,
+    }
+}
+
+/// The name of a control sequence as TeX prints it, with `escape` in place of
+/// the leading backslash. Active characters are printed as they are.
+pub fn escaped(name: &str, escape: Option<char>) -> String {
+    match name.strip_prefix('\\') {
+        Some(rest) => escape.into_iter().chain(rest.chars()).collect(),
+        None => name.to_string(),
     }
+}
 
-    fn run(&self, state: &mut TexState) -> Result<(), Error> {
-        // match_pattern(&self.parameters, state);
-        todo!("{}", self.name());
+/// A control sequence or active character as TeX prints it.
+pub(crate) fn escaped_symbol(name: Symbol, escape: Option<char>) -> String {
+    if name.is_active() {
+        name.name().to_string()
+    } else {
+        escaped(name.name(), escape)
     }
+}
 
-    fn safe(&self, _: &TexState) -> bool {
-        todo!()
+/// Shows a token list the way TeX prints it, with a space after every
+/// control word.
+pub(crate) fn show_tokens(tokens: &[Token], escape: Option<char>) -> String {
+    let mut out = String::new();
+    for t in tokens {
+        match t {
+            Token::ControlSequence(name) => {
+                out.push_str(&escaped_symbol(*name, escape));
+                let name = name.name();
+                if name.chars().count() > 2
+                    || name.chars().nth(1).is_some_and(|c| c.is_alphabetic())
+                {
+                    out.push(' ');
+                }
+            }
+            Token::Parameter(c, n) => {
+                out.push(*c);
+                out.push_str(&n.to_string());
+            }
+            Token::Character(c, CharacterCategory::Parameter) => {
+                out.push(*c);
+                out.push(*c);
+            }
+            Token::Character(c, _) => out.push(*c),
+        }
     }
+    out
 }
 #[derive(Clone, Debug)]
-pub struct MacroMap(HashMap<String, Box<dyn Macro>>);
+pub struct MacroMap(HashMap<Symbol, Option<Command>>);
+
+impl IntoIterator for MacroMap {
+    type Item = (Symbol, Option<Command>);
+    type IntoIter = std::collections::hash_map::IntoIter<Symbol, Option<Command>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
 
 impl MacroMap {
     pub fn new() -> Self {
         MacroMap(HashMap::new())
     }
     pub fn init(&mut self) {
+        use primitives::*;
+        for kind in DefKind::ALL {
+            self.primitive(Command::Unexpandable(Box::new(Def(kind))));
+        }
+        self.primitive(Command::Unexpandable(Box::new(Par)));
+        self.primitive(Command::Unexpandable(Box::new(Relax)));
+        for mode in InteractionMode::ALL {
+            self.primitive(Command::Unexpandable(Box::new(Interaction(mode))));
+        }
+        for kind in PrefixKind::ALL {
+            self.primitive(Command::Unexpandable(Box::new(Prefix(kind))));
+        }
+        self.primitive(Command::Unexpandable(Box::new(Catcode)));
+        self.primitive(Command::Unexpandable(Box::new(BeginGroup)));
+        self.primitive(Command::Unexpandable(Box::new(EndGroup)));
+        self.primitive(Command::Unexpandable(Box::new(BoxRegister)));
+        self.primitive(Command::Unexpandable(Box::new(Let)));
+        self.primitive(Command::Unexpandable(Box::new(FutureLet)));
+        self.primitive(Command::Unexpandable(Box::new(CharDef)));
+        self.primitive(Command::Unexpandable(Box::new(MathCharDef)));
+        self.primitive(Command::Expandable(Box::new(NoExpand)));
+        self.primitive(Command::Expandable(Box::new(ExpandAfter)));
+        self.primitive(Command::Unexpandable(Box::new(AfterAssignment)));
+        self.primitive(Command::Unexpandable(Box::new(AfterGroup)));
+        for kind in RegisterKind::ALL {
+            self.primitive(Command::Unexpandable(Box::new(Register(kind))));
+            self.primitive(Command::Unexpandable(Box::new(RegisterDef(kind))));
+        }
+        for kind in IfKind::ALL {
+            self.primitive(Command::Expandable(Box::new(IfTest(kind))));
+        }
+        for code in [ConditionCode::Fi, ConditionCode::Else, ConditionCode::Or] {
+            self.primitive(Command::Expandable(Box::new(FiOrElse(code))));
+        }
+        self.primitive(Command::Expandable(Box::new(CsName)));
+        self.primitive(Command::Unexpandable(Box::new(EndCsName)));
+        self.primitive(Command::Expandable(Box::new(StringOf)));
+        self.primitive(Command::Expandable(Box::new(MeaningOf)));
+        self.primitive(Command::Expandable(Box::new(The)));
+        self.primitive(Command::Expandable(Box::new(InputFile)));
+        self.primitive(Command::Expandable(Box::new(EndInput)));
+        self.primitive(Command::Unexpandable(Box::new(InputLineNo)));
+        for p in IntegerParameter::ALL {
+            self.primitive(Command::Unexpandable(Box::new(IntegerParam(p))));
+        }
+        for p in TokenParameter::ALL {
+            self.primitive(Command::Unexpandable(Box::new(TokenParam(p))));
+        }
+        self.primitive(Command::Unexpandable(Box::new(Message)));
+        self.primitive(Command::Unexpandable(Box::new(ErrMessage)));
+        for kind in ShowKind::ALL {
+            self.primitive(Command::Unexpandable(Box::new(Show(kind))));
+        }
+    }
+    /// Defines a primitive under its own name.
+    fn primitive(&mut self, command: Command) {
         self.0
-            .insert(r"\def".to_string(), Box::new(primitives::Def));
+            .insert(Symbol::intern(&command.name()), Some(command));
     }
     pub fn new_and_init() -> Self {
         let mut map = Self::new();
         map.init();
         map
     }
-    pub fn get(&self, s: &String) -> Option<&Box<dyn Macro>> {
-        self.0.get(s)
+    /// The meaning of `s` in this map: `None` if it is not in the map, and
+    /// `Some(None)` if it has been made undefined.
+    pub fn get(&self, s: Symbol) -> Option<&Option<Command>> {
+        self.0.get(&s)
     }
-    pub fn contains(&self, s: String) -> bool {
+    pub fn contains(&self, s: Symbol) -> bool {
         self.0.contains_key(&s)
     }
-    pub fn set(&mut self, s: String, mcro: Box<dyn Macro>) {
+    pub fn set(&mut self, s: Symbol, mcro: Option<Command>) {
         self.0.insert(s, mcro);
     }
+    pub fn remove(&mut self, s: Symbol) {
+        self.0.remove(&s);
+    }
 }
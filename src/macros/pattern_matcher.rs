@@ -1,163 +1,232 @@
-use std::{
-    fmt::Debug,
-    ops::{Add, Rem},
+use super::{is_outer, UserDefinedMacro};
+use crate::{
+    errors::{Error, ErrorKind},
+    parser::{lexer::CharacterCategory, parser::Token, symbol::Symbol},
+    TexState,
 };
 
-use crate::{errors::Error, parser::parser::Token, TexState};
+/// A piece of a macro's parameter text: either a run of constant tokens that
+/// has to appear literally in the input, or a parameter `#n` together with the
+/// tokens delimiting it (empty for undelimited parameters).
+#[derive(Debug)]
+enum Section<'a, T: PartialEq> {
+    Constants(&'a [T]),
+    Parameter(u8, &'a [T]),
+}
 
-struct OptionalZipper<A, B, C, D>
-where
-    A: Iterator<Item = C>,
-    B: Iterator<Item = D>,
-{
-    a: A,
-    b: B,
+/// Splits a parameter text into its leading constants followed by one
+/// [`Section::Parameter`] per parameter.
+fn sections(target: &[Token]) -> Vec<Section<'_, Token>> {
+    let mut sections = Vec::new();
+    let first = target
+        .iter()
+        .position(|t| matches!(t, Token::Parameter(_, _)))
+        .unwrap_or(target.len());
+    if first > 0 {
+        sections.push(Section::Constants(&target[..first]));
+    }
+    let mut i = first;
+    while i < target.len() {
+        let n = match target[i] {
+            Token::Parameter(_, n) => n,
+            _ => unreachable!(),
+        };
+        let end = target[i + 1..]
+            .iter()
+            .position(|t| matches!(t, Token::Parameter(_, _)))
+            .map(|p| p + i + 1)
+            .unwrap_or(target.len());
+        sections.push(Section::Parameter(n, &target[i + 1..end]));
+        i = end;
+    }
+    sections
+}
+
+fn is_begin_group(t: &Token) -> bool {
+    matches!(t, Token::Character(_, CharacterCategory::BeginGroup))
+}
+fn is_end_group(t: &Token) -> bool {
+    matches!(t, Token::Character(_, CharacterCategory::EndGroup))
 }
-impl<A, B, C, D> OptionalZipper<A, B, C, D>
-where
-    A: Iterator<Item = C>,
-    B: Iterator<Item = D>,
-{
-    fn new(a: A, b: B) -> Self {
-        Self { a, b }
+
+/// Reads the next unexpanded token of an argument of `m`, turning the end of
+/// the input, `\par` in the arguments of a macro that is not `\long` and
+/// `\outer` macros into runaway argument errors.
+fn next(m: &UserDefinedMacro, state: &mut TexState) -> Result<Token, Error> {
+    let t = state.get_element().map_err(|e| match e.kind() {
+        ErrorKind::EndOfFile => Error::new(
+            ErrorKind::Runaway,
+            format!("File ended while scanning use of {}", m.name),
+        ),
+        _ => e,
+    })?;
+    if !m.long && t == Token::ControlSequence(Symbol::intern(r"\par")) {
+        return Err(Error::new(
+            ErrorKind::ParagraphEnded,
+            format!("Paragraph ended before {} was complete", m.name),
+        ));
+    }
+    if is_outer(state, &t) {
+        return Err(Error::new(
+            ErrorKind::ForbiddenControlSequence,
+            format!(
+                "Forbidden control sequence found while scanning use of {}",
+                m.name
+            ),
+        ));
     }
+    Ok(t)
 }
-impl<A, B, C, D> Iterator for OptionalZipper<A, B, C, D>
-where
-    A: Iterator<Item = C>,
-    B: Iterator<Item = D>,
-{
-    type Item = (Option<C>, Option<D>);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match (self.a.next(), self.b.next()) {
-            (None, None) => None,
-            a => Some(a),
+/// Reads tokens up to the `}` matching an already consumed `{`, appending
+/// everything before it to `arg` and returning the `}` itself.
+fn scan_balanced(
+    m: &UserDefinedMacro,
+    arg: &mut Vec<Token>,
+    state: &mut TexState,
+) -> Result<Token, Error> {
+    let mut level = 0usize;
+    loop {
+        let t = next(m, state)?;
+        if is_begin_group(&t) {
+            level += 1;
+        } else if is_end_group(&t) {
+            if level == 0 {
+                return Ok(t);
+            }
+            level -= 1;
         }
+        arg.push(t);
     }
 }
 
-struct PatternMatchResult<T> {
-    map: Vec<Vec<T>>,
-}
-impl<T> PatternMatchResult<T> {
-    fn new(n: u8) -> Self {
-        Self {
-            map: Vec::with_capacity(n.into()),
+/// Scans an undelimited argument: spaces are skipped, then either a single
+/// token or a balanced group (without its outer braces) is taken.
+fn scan_undelimited(m: &UserDefinedMacro, state: &mut TexState) -> Result<Vec<Token>, Error> {
+    loop {
+        let t = next(m, state)?;
+        if matches!(t, Token::Character(_, CharacterCategory::Space)) {
+            continue;
+        }
+        if is_end_group(&t) {
+            return Err(Error::new(
+                ErrorKind::ExtraRightBraceInArgument,
+                format!("Argument of {} has an extra }}", m.name),
+            ));
         }
+        if is_begin_group(&t) {
+            let mut arg = Vec::new();
+            scan_balanced(m, &mut arg, state)?;
+            return Ok(arg);
+        }
+        return Ok(vec![t]);
     }
 }
 
-#[derive(Debug)]
-enum Section<'a, T: PartialEq> {
-    Constants(&'a [T]),
-    Parameter(u8, bool),
-}
-impl<'a, T> Section<'a, T>
-where
-    T: PartialEq,
-{
-    fn matches(&self, tokens: &'a [T], min_length: usize) -> Result<(&'a [T], &'a [T]), ()> {
-        match self {
-            Self::Constants(a) => {
-                for (target, actual) in OptionalZipper::new(a.iter(), tokens.iter()) {
-                    if target != actual {
-                        return Err(());
-                    }
-                }
-                Ok((&tokens[..a.len()], &tokens[a.len()..]))
+/// Scans a delimited argument, i.e. everything up to the first occurrence of
+/// `delimiter` at brace level zero. Partial matches of the delimiter that turn
+/// out to fail are contributed to the argument, taking care of overlaps the
+/// same way TeX does.
+fn scan_delimited(
+    m: &UserDefinedMacro,
+    delimiter: &[Token],
+    state: &mut TexState,
+) -> Result<Vec<Token>, Error> {
+    let mut arg = Vec::new();
+    // Number of delimiter tokens matched so far.
+    let mut matched = 0;
+    // Number of top level units (tokens or groups) in the argument.
+    let mut units = 0usize;
+    loop {
+        let t = next(m, state)?;
+        if t == delimiter[matched] {
+            matched += 1;
+            if matched == delimiter.len() {
+                break;
             }
-            Self::Parameter(_, a) => {
-                let min_length = if min_length == 0 && *a { 1 } else { min_length }.into();
-                if tokens.len() < min_length {
-                    Err(())
-                } else {
-                    Ok((&tokens[..min_length], &tokens[min_length..]))
+            continue;
+        }
+        if matched > 0 {
+            // Find the longest proper suffix of the matched part that is again
+            // a prefix of the delimiter and can be continued by `t`.
+            let restart = (0..matched)
+                .rev()
+                .find(|&k| delimiter[matched - k..matched] == delimiter[..k] && delimiter[k] == t)
+                .map(|k| k + 1);
+            let keep = restart.map(|k| k - 1).unwrap_or(0);
+            arg.extend_from_slice(&delimiter[..matched - keep]);
+            units += matched - keep;
+            if let Some(k) = restart {
+                matched = k;
+                if matched == delimiter.len() {
+                    break;
                 }
+                continue;
             }
+            matched = 0;
         }
-    }
-}
-fn try_with<'a, T: PartialEq + Debug>(
-    target: &'a [Section<'a, T>],
-    mut actual: &'a [T],
-    lengths: &'a [usize],
-) -> Result<PatternMatchResult<T>, ()> {
-    let tagetlen: usize = target
-        .iter()
-        .map(|s| match s {
-            Section::Constants(items) => items.len(),
-            Section::Parameter(n, a) => lengths[*n as usize].max(if *a { 1 } else { 0 }),
-        })
-        .sum();
-    if actual.len() < tagetlen {
-        return Err(());
-    }
-    for t in target {
-        let a: &'a [T];
-        (a, actual) = match t {
-            t @ Section::Constants(_) => t.matches(actual, 0)?,
-            t @ Section::Parameter(parn, _) => t.matches(actual, lengths[*parn as usize])?,
-        };
-        dbg!(a);
-    }
-
-    todo!();
-}
-trait Number: Add<Output = Self> + PartialEq + PartialOrd + Copy + Clone + Rem<Output = Self> {}
-impl Number for usize {}
-struct Counter<T>
-where
-    T: Number,
-{
-    count: usize,
-    current: Vec<T>,
-    maximum: T,
-    rollover: T,
-    increase: T,
-}
-impl<T> Counter<T>
-where
-    T: Number,
-{
-    fn new(count: usize, maximum: T, initial: T, increase: T) -> Counter<T> {
-        let mut v = Vec::with_capacity(count);
-        for _ in 0..count {
-            v.push(initial);
+        if is_end_group(&t) {
+            return Err(Error::new(
+                ErrorKind::ExtraRightBraceInArgument,
+                format!("Argument of {} has an extra }}", m.name),
+            ));
         }
-        Counter {
-            count,
-            maximum,
-            current: v,
-            rollover: initial,
-            increase,
+        let is_group = is_begin_group(&t);
+        arg.push(t);
+        if is_group {
+            let end = scan_balanced(m, &mut arg, state)?;
+            arg.push(end);
         }
+        units += 1;
     }
+    // A delimited argument consisting of exactly one group loses its braces.
+    if units == 1 && arg.len() >= 2 && is_begin_group(&arg[0]) && is_end_group(&arg[arg.len() - 1])
+    {
+        arg.pop();
+        arg.remove(0);
+    }
+    Ok(arg)
 }
-impl<T> Iterator for Counter<T>
-where
-    T: Number,
-{
-    type Item = Vec<T>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        for m in (0..self.count).rev() {
-            let mut nv = self.current[m] + self.increase;
-            if nv >= self.maximum {
-                if m == 0 {
-                    return None;
+/// Matches the parameter text of the macro `m` against the input of `state`,
+/// returning the arguments indexed by parameter number minus one.
+pub fn match_pattern(m: &UserDefinedMacro, state: &mut TexState) -> Result<Vec<Vec<Token>>, Error> {
+    let mut arguments = Vec::new();
+    for section in sections(&m.parameters) {
+        match section {
+            Section::Constants(constants) => {
+                for expected in constants {
+                    let t = next(m, state)?;
+                    if &t != expected {
+                        return Err(Error::new(
+                            ErrorKind::DoesNotMatchDefinition,
+                            format!("Use of {} doesn't match its definition", m.name),
+                        ));
+                    }
                 }
-                nv = nv % self.maximum;
-                self.current[m] = nv;
-            } else {
-                self.current[m] = nv;
-                break;
+            }
+            Section::Parameter(n, delimiter) => {
+                debug_assert_eq!(n as usize, arguments.len() + 1);
+                arguments.push(if delimiter.is_empty() {
+                    scan_undelimited(m, state)?
+                } else {
+                    scan_delimited(m, delimiter, state)?
+                });
             }
         }
-        Some(self.current.clone())
     }
+    Ok(arguments)
 }
 
-pub fn match_pattern<'a>(target: &'a Vec<Token>, state: &mut TexState) -> Result<(), Error> {
-    todo!();
+/// Replaces the parameter tokens `#n` in `replacements` by the corresponding
+/// arguments.
+pub fn substitute(replacements: &[Token], arguments: &[Vec<Token>]) -> Vec<Token> {
+    let mut result = Vec::with_capacity(replacements.len());
+    for t in replacements {
+        match t {
+            Token::Parameter(_, n) => result.extend_from_slice(&arguments[*n as usize - 1]),
+            t => result.push(*t),
+        }
+    }
+    result
 }
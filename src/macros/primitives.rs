@@ -1,62 +1,887 @@
+use crate::errors::ErrorKind;
+use crate::parameters::{IntegerParameter, RegisterKind, TokenParameter};
 use crate::parser::{lexer::CharacterCategory, parser::Token};
+use crate::GroupKind;
 
 use super::*;
+/// The definition commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefKind {
+    Def,
+    Gdef,
+    Edef,
+    Xdef,
+}
+
+impl DefKind {
+    pub const ALL: [DefKind; 4] = [DefKind::Def, DefKind::Gdef, DefKind::Edef, DefKind::Xdef];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DefKind::Def => r"\def",
+            DefKind::Gdef => r"\gdef",
+            DefKind::Edef => r"\edef",
+            DefKind::Xdef => r"\xdef",
+        }
+    }
+    /// Whether the definition is always global.
+    pub fn global(&self) -> bool {
+        matches!(self, DefKind::Gdef | DefKind::Xdef)
+    }
+    /// Whether the replacement text is fully expanded.
+    pub fn expand(&self) -> bool {
+        matches!(self, DefKind::Edef | DefKind::Xdef)
+    }
+}
+
+/// Reads the control sequence that a definition or assignment such as `\def`
+/// or `\let` gives a meaning to.
+fn get_r_token(state: &mut TexState) -> Result<Symbol, Error> {
+    match state.get_element()? {
+        Token::ControlSequence(name) => Ok(name),
+        t => Err(Error::new(
+            ErrorKind::MissingControlSequence,
+            format!("Missing control sequence inserted before {}", t.to_string()),
+        )),
+    }
+}
+
+/// Reads the next token of the definition of `name`, expanding it if
+/// `expand` is set, and rejecting `\outer` macros and the end of the input.
+fn next_in_definition(state: &mut TexState, name: &str, expand: bool) -> Result<Token, Error> {
+    let t = if expand {
+        state.get_x_or_protected()
+    } else {
+        state.get_element()
+    }
+    .map_err(|e| match e.kind() {
+        ErrorKind::EndOfFile => Error::new(
+            ErrorKind::Runaway,
+            format!("File ended while scanning definition of {}", name),
+        ),
+        _ => e,
+    })?;
+    if is_outer(state, &t) {
+        return Err(Error::new(
+            ErrorKind::ForbiddenControlSequence,
+            format!(
+                "Forbidden control sequence found while scanning definition of {}",
+                name
+            ),
+        ));
+    }
+    Ok(t)
+}
+
+/// `\def`, `\gdef`, `\edef` and `\xdef`.
 #[derive(Clone, Debug)]
-pub struct Def;
+pub struct Def(pub DefKind);
 
 impl Macro for Def {
     fn name(&self) -> String {
-        return r"\def".to_string();
+        self.0.name().to_string()
     }
+}
 
-    fn run(&self, state: &mut TexState) -> Result<(), Error> {
-        state.push_group();
-        let command = state.get_element()?;
+impl Unexpandable for Def {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
         let mut parameters: Vec<Token> = Vec::new();
         let mut parameter_count = 0;
-        let mut replacements = Vec::new();
+        // The `{` of a `#{` parameter text, which is both the final delimiter
+        // and the last token of the replacement text.
+        let mut hash_brace = None;
         loop {
-            match state.get_element()? {
-                t @ Token::Character(_, CharacterCategory::BeginGroup) => {
-                    replacements.push(t);
-                    break;
+            match next_in_definition(state, name.name(), false)? {
+                Token::Character(_, CharacterCategory::BeginGroup) => break,
+                Token::Character(_, CharacterCategory::EndGroup) => {
+                    return Err(Error::new(
+                        ErrorKind::MissingLeftBrace,
+                        format!("Missing {{ inserted in definition of {}", name),
+                    ))
                 }
-                a @ Token::Parameter(_, _) => {
-                    parameter_count += 1;
-                    parameters.push(a);
+                Token::Character(c, CharacterCategory::Parameter) => {
+                    match next_in_definition(state, name.name(), false)? {
+                        t @ Token::Character(_, CharacterCategory::BeginGroup) => {
+                            parameters.push(t);
+                            hash_brace = Some(t);
+                            break;
+                        }
+                        _ if parameter_count == 9 => {
+                            return Err(Error::new(
+                                ErrorKind::TooManyParameters,
+                                "You already have nine parameters".to_string(),
+                            ))
+                        }
+                        Token::Character(d, CharacterCategory::Other)
+                            if d.to_digit(10) == Some(parameter_count as u32 + 1) =>
+                        {
+                            parameter_count += 1;
+                            parameters.push(Token::Parameter(c, parameter_count));
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::NonConsecutiveParameters,
+                                "Parameters must be numbered consecutively".to_string(),
+                            ))
+                        }
+                    }
                 }
                 a => {
                     parameters.push(a);
                 }
             }
         }
-        state.push_group();
 
+        let expand = self.0.expand();
+        let mut replacements = Vec::new();
+        let mut level = 0usize;
         loop {
-            match state.get_element()? {
+            match next_in_definition(state, name.name(), expand)? {
+                t @ Token::Character(_, CharacterCategory::BeginGroup) => {
+                    level += 1;
+                    replacements.push(t);
+                }
                 t @ Token::Character(_, CharacterCategory::EndGroup) => {
+                    if level == 0 {
+                        break;
+                    }
+                    level -= 1;
                     replacements.push(t);
-                    break;
+                }
+                Token::Character(c, CharacterCategory::Parameter) => {
+                    match next_in_definition(state, name.name(), expand)? {
+                        t @ Token::Character(_, CharacterCategory::Parameter) => {
+                            replacements.push(t)
+                        }
+                        Token::Character(d, CharacterCategory::Other)
+                            if (1..=parameter_count as u32)
+                                .contains(&d.to_digit(10).unwrap_or(0)) =>
+                        {
+                            replacements.push(Token::Parameter(c, d.to_digit(10).unwrap() as u8));
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::IllegalParameterNumber,
+                                format!("Illegal parameter number in definition of {}", name),
+                            ))
+                        }
+                    }
                 }
                 a => {
                     replacements.push(a);
                 }
             }
         }
+        if let Some(t) = hash_brace {
+            replacements.push(t);
+        }
+
+        let prefixes = state.prefixes;
+        if self.0.global() {
+            state.prefixes.global = true;
+        }
+        state.define(
+            name,
+            Some(Command::Expandable(Box::new(UserDefinedMacro::new(
+                name,
+                parameters,
+                replacements,
+                parameter_count,
+                prefixes,
+            )))),
+        );
+
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Definition
+    }
+}
+
+/// `\par`, which the input reader produces for empty lines. Without paragraph
+/// building there is nothing for it to do yet.
+#[derive(Clone, Debug)]
+pub struct Par;
+
+impl Macro for Par {
+    fn name(&self) -> String {
+        r"\par".to_string()
+    }
+}
+
+impl Unexpandable for Par {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        // As in TeX, the error count starts over with every paragraph.
+        state.error_count = 0;
+        Ok(())
+    }
+}
+
+/// `\batchmode`, `\nonstopmode`, `\scrollmode` and `\errorstopmode`.
+#[derive(Clone, Debug)]
+pub struct Interaction(pub InteractionMode);
+
+impl Macro for Interaction {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for Interaction {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.interaction = self.0;
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\relax`, which does nothing.
+#[derive(Clone, Debug)]
+pub struct Relax;
+
+impl Macro for Relax {
+    fn name(&self) -> String {
+        r"\relax".to_string()
+    }
+}
+
+impl Unexpandable for Relax {
+    fn execute(&self, _: &mut TexState) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The prefixes that may precede assignments and definitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixKind {
+    Global,
+    Long,
+    Outer,
+    Protected,
+}
+
+impl PrefixKind {
+    pub const ALL: [PrefixKind; 4] = [
+        PrefixKind::Global,
+        PrefixKind::Long,
+        PrefixKind::Outer,
+        PrefixKind::Protected,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrefixKind::Global => r"\global",
+            PrefixKind::Long => r"\long",
+            PrefixKind::Outer => r"\outer",
+            PrefixKind::Protected => r"\protected",
+        }
+    }
+}
+
+/// `\global`, `\long`, `\outer` and `\protected`, which modify the
+/// following assignment or definition.
+#[derive(Clone, Debug)]
+pub struct Prefix(pub PrefixKind);
+
+impl Macro for Prefix {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for Prefix {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        match self.0 {
+            PrefixKind::Global => state.prefixes.global = true,
+            PrefixKind::Long => state.prefixes.long = true,
+            PrefixKind::Outer => state.prefixes.outer = true,
+            PrefixKind::Protected => state.prefixes.protected = true,
+        }
+        let result = execute_prefixed(state);
+        state.prefixes = Default::default();
+        result
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Prefix
+    }
+}
+
+/// Executes the command following a prefix, if it accepts the prefixes given.
+fn execute_prefixed(state: &mut TexState) -> Result<(), Error> {
+    let t = loop {
+        let t = state.get_x_non_blank()?;
+        match &t {
+            Token::ControlSequence(name)
+                if state
+                    .state
+                    .get_macro(*name)
+                    .is_some_and(|m| m.meaning() == r"\relax") => {}
+            _ => break t,
+        }
+    };
+    let prefixing = match &t {
+        Token::ControlSequence(name) => match state.state.get_macro(*name) {
+            Some(Command::Unexpandable(m)) => m.prefixing(),
+            _ => Prefixing::None,
+        },
+        _ => Prefixing::None,
+    };
+    let prefixes = state.prefixes;
+    match prefixing {
+        Prefixing::None => Err(Error::new(
+            ErrorKind::PrefixNotAllowed,
+            format!("You can't use a prefix with `{}'", t.to_string()),
+        )),
+        Prefixing::Assignment if prefixes.long || prefixes.outer || prefixes.protected => {
+            Err(Error::new(
+                ErrorKind::LongOrOuterNotAllowed,
+                format!(
+                    r"You can't use `\long' or `\outer' or `\protected' with `{}'",
+                    t.to_string()
+                ),
+            ))
+        }
+        _ => state.execute_token(t),
+    }
+}
+
+/// `\catcode`, both as an assignment and as an internal integer.
+#[derive(Clone, Debug)]
+pub struct Catcode;
+
+impl Macro for Catcode {
+    fn name(&self) -> String {
+        r"\catcode".to_string()
+    }
+}
+
+impl Unexpandable for Catcode {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let chr = state.scan_char_num()?;
+        state.scan_optional_equals()?;
+        let code = state.scan_int()?;
+        let cat = match CharacterCategory::from_code(code) {
+            Some(cat) => cat,
+            None => {
+                state.recoverable_error(Error::new(
+                    ErrorKind::InvalidCode,
+                    format!("Invalid code ({}), should be between 0 and 15", code),
+                ))?;
+                CharacterCategory::Escape
+            }
+        };
+        let global = state.global_assignment();
+        state.state.set_category_with_global(chr, cat, global);
+        Ok(())
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        let chr = state.scan_char_num()?;
+        Ok(Some(Value::Integer(state.state.get_category(chr).code())))
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// An integer parameter such as `\endlinechar`, both as an assignment and as
+/// an internal integer.
+#[derive(Clone, Debug)]
+pub struct IntegerParam(pub IntegerParameter);
+
+impl Macro for IntegerParam {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for IntegerParam {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.scan_optional_equals()?;
+        let value = state.scan_int()?;
+        let global = state.global_assignment();
+        state.state.set_integer_with_global(self.0, value, global);
+        Ok(())
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::Integer(state.state.get_integer(self.0))))
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// A token list parameter such as `\errhelp`, both as an assignment and as
+/// an internal token list.
+#[derive(Clone, Debug)]
+pub struct TokenParam(pub TokenParameter);
+
+impl Macro for TokenParam {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for TokenParam {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.scan_optional_equals()?;
+        let tokens = state.scan_token_list_value()?;
+        let global = state.global_assignment();
+        state.state.set_tokens_with_global(self.0, tokens, global);
+        Ok(())
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::Tokens(state.state.get_tokens(self.0))))
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\begingroup`, which starts a group that is ended by `\endgroup`.
+#[derive(Clone, Debug)]
+pub struct BeginGroup;
+
+impl Macro for BeginGroup {
+    fn name(&self) -> String {
+        r"\begingroup".to_string()
+    }
+}
+
+impl Unexpandable for BeginGroup {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.push_group(GroupKind::SemiSimple);
+        Ok(())
+    }
+}
+
+/// `\endgroup`, which ends a group started by `\begingroup`.
+#[derive(Clone, Debug)]
+pub struct EndGroup;
+
+impl Macro for EndGroup {
+    fn name(&self) -> String {
+        r"\endgroup".to_string()
+    }
+}
+
+impl Unexpandable for EndGroup {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.handle_end_group()
+    }
+}
+
+/// Scans the right-hand side of an assignment to register `n` of the given
+/// kind and performs the assignment.
+fn assign_register(state: &mut TexState, kind: RegisterKind, n: u16) -> Result<(), Error> {
+    state.scan_optional_equals()?;
+    let value = match kind {
+        RegisterKind::Count => Value::Integer(state.scan_int()?),
+        RegisterKind::Dimen => Value::Dimension(state.scan_dimen()?),
+        RegisterKind::Skip => Value::Glue(state.scan_glue(false)?),
+        RegisterKind::MuSkip => Value::MuGlue(state.scan_glue(true)?),
+        RegisterKind::Toks => Value::Tokens(state.scan_token_list_value()?),
+    };
+    let global = state.global_assignment();
+    state.state.set_register_with_global(kind, n, value, global);
+    Ok(())
+}
+
+/// A register bank such as `\count`, followed by the number of a register.
+#[derive(Clone, Debug)]
+pub struct Register(pub RegisterKind);
+
+impl Macro for Register {
+    fn name(&self) -> String {
+        self.0.name().to_string()
+    }
+}
+
+impl Unexpandable for Register {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let n = state.scan_register_num()?;
+        assign_register(state, self.0, n)
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        let n = state.scan_register_num()?;
+        Ok(Some(state.state.get_register(self.0, n)))
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// A control sequence defined by `\countdef` and friends, which stands for a
+/// single register.
+#[derive(Clone, Debug)]
+pub struct RegisterRef {
+    pub kind: RegisterKind,
+    pub index: u16,
+}
+
+impl Macro for RegisterRef {
+    fn name(&self) -> String {
+        format!("{}{}", self.kind.name(), self.index)
+    }
+}
+
+impl Unexpandable for RegisterRef {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        assign_register(state, self.kind, self.index)
+    }
+
+    fn value(&self, state: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(state.state.get_register(self.kind, self.index)))
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\countdef`, `\dimendef`, `\skipdef`, `\muskipdef` and `\toksdef`.
+#[derive(Clone, Debug)]
+pub struct RegisterDef(pub RegisterKind);
+
+impl Macro for RegisterDef {
+    fn name(&self) -> String {
+        self.0.def_name().to_string()
+    }
+}
+
+impl Unexpandable for RegisterDef {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
+        state.scan_optional_equals()?;
+        let index = state.scan_register_num()?;
+        state.define(
+            name,
+            Some(Command::Unexpandable(Box::new(RegisterRef {
+                kind: self.0,
+                index,
+            }))),
+        );
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\box`, which takes the contents of a box register and leaves it void.
+#[derive(Clone, Debug)]
+pub struct BoxRegister;
+
+impl Macro for BoxRegister {
+    fn name(&self) -> String {
+        r"\box".to_string()
+    }
+}
+
+impl Unexpandable for BoxRegister {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let n = state.scan_register_num()?;
+        // There are no lists to append the box to yet.
+        state.state.take_box(n);
+        Ok(())
+    }
+}
+
+/// `\noexpand`, which keeps the following token from being expanded when it
+/// is read next.
+#[derive(Clone, Debug)]
+pub struct NoExpand;
+
+impl Macro for NoExpand {
+    fn name(&self) -> String {
+        r"\noexpand".to_string()
+    }
+}
+
+impl Expandable for NoExpand {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let t = state.get_element()?;
+        let expandable = match &t {
+            Token::ControlSequence(name) => state
+                .state
+                .get_macro(*name)
+                .is_some_and(|m| m.is_expandable()),
+            _ => false,
+        };
+        if expandable {
+            state.push_tokens(TokenListKind::NoExpand, vec![t])
+        } else {
+            state.back_input(t)
+        }
+    }
+}
+
+/// `\expandafter`, which expands the token after next before reading the
+/// next one.
+#[derive(Clone, Debug)]
+pub struct ExpandAfter;
+
+impl Macro for ExpandAfter {
+    fn name(&self) -> String {
+        r"\expandafter".to_string()
+    }
+}
+
+impl Expandable for ExpandAfter {
+    fn expand(&self, state: &mut TexState) -> Result<(), Error> {
+        let first = state.get_element()?;
+        let (second, expand) = state.get_next()?;
+        let command = match &second {
+            Token::ControlSequence(name) => state.state.get_macro(*name).cloned(),
+            _ => None,
+        };
+        match command {
+            Some(Command::Expandable(m)) if expand => m.expand(state)?,
+            Some(Command::Expandable(_)) => {
+                state.push_tokens(TokenListKind::NoExpand, vec![second])?
+            }
+            _ => state.back_input(second)?,
+        }
+        state.back_input(first)
+    }
+}
+
+/// `\afterassignment`, which saves a token to be read after the next
+/// assignment.
+#[derive(Clone, Debug)]
+pub struct AfterAssignment;
+
+impl Macro for AfterAssignment {
+    fn name(&self) -> String {
+        r"\afterassignment".to_string()
+    }
+}
+
+impl Unexpandable for AfterAssignment {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.after_assignment = Some(state.get_element()?);
+        Ok(())
+    }
+}
+
+/// `\aftergroup`, which saves a token to be read after the current group
+/// ends.
+#[derive(Clone, Debug)]
+pub struct AfterGroup;
+
+impl Macro for AfterGroup {
+    fn name(&self) -> String {
+        r"\aftergroup".to_string()
+    }
+}
+
+impl Unexpandable for AfterGroup {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let t = state.get_element()?;
+        state.state.after_group(t);
+        Ok(())
+    }
+}
+
+/// The meaning a token has: that of the command it names if it is a control
+/// sequence (`None` if it is undefined), or the character itself otherwise.
+fn meaning_of(state: &TexState, t: &Token) -> Option<Command> {
+    match t {
+        Token::ControlSequence(name) => state.state.get_macro(*name).cloned(),
+        Token::Character(c, cat) => Some(Command::Character(*c, *cat)),
+        Token::Parameter(c, _) => Some(Command::Character(*c, CharacterCategory::Parameter)),
+    }
+}
+
+/// `\let`, which gives a control sequence the current meaning of a token.
+#[derive(Clone, Debug)]
+pub struct Let;
+
+impl Macro for Let {
+    fn name(&self) -> String {
+        r"\let".to_string()
+    }
+}
+
+impl Unexpandable for Let {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
+        let mut t = state.get_element()?;
+        while let Token::Character(_, CharacterCategory::Space) = t {
+            t = state.get_element()?;
+        }
+        // One optional space is allowed after the `=`.
+        if let Token::Character('=', CharacterCategory::Other) = t {
+            t = state.get_element()?;
+            if let Token::Character(_, CharacterCategory::Space) = t {
+                t = state.get_element()?;
+            }
+        }
+        let meaning = meaning_of(state, &t);
+        state.define(name, meaning);
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\futurelet`, which gives a control sequence the meaning of the token
+/// after next, and then reads both tokens again.
+#[derive(Clone, Debug)]
+pub struct FutureLet;
 
-        state.pop_group()?;
-        state.pop_group()?;
-        state.define(Box::new(UserDefinedMacro {
-            name: command.to_string(),
-            parameters,
-            replacements,
-            parameter_count,
-        }));
+impl Macro for FutureLet {
+    fn name(&self) -> String {
+        r"\futurelet".to_string()
+    }
+}
 
+impl Unexpandable for FutureLet {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
+        let first = state.get_element()?;
+        let second = state.get_element()?;
+        let meaning = meaning_of(state, &second);
+        state.back_input(second)?;
+        state.back_input(first)?;
+        state.define(name, meaning);
         Ok(())
     }
 
-    fn safe(&self, _: &TexState) -> bool {
-        todo!()
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// `\chardef`, which defines a control sequence to stand for a character code.
+#[derive(Clone, Debug)]
+pub struct CharDef;
+
+impl Macro for CharDef {
+    fn name(&self) -> String {
+        r"\chardef".to_string()
+    }
+}
+
+impl Unexpandable for CharDef {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
+        // The name means `\relax` while the number is scanned, so that
+        // `\chardef\x=\x` does not loop.
+        let global = state.prefixes.global;
+        state.define(name, Some(Command::Unexpandable(Box::new(Relax))));
+        state.prefixes.global = global;
+        state.scan_optional_equals()?;
+        let c = state.scan_char_num()?;
+        state.define(name, Some(Command::Unexpandable(Box::new(CharGiven(c)))));
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// A control sequence defined by `\chardef`. It typesets its character and
+/// stands for its code when used as a number.
+#[derive(Clone, Debug)]
+pub struct CharGiven(pub char);
+
+impl Macro for CharGiven {
+    fn name(&self) -> String {
+        format!("\\char\"{:X}", self.0 as u32)
+    }
+}
+
+impl Unexpandable for CharGiven {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        state.execute_token(Token::Character(self.0, CharacterCategory::Other))
+    }
+
+    fn value(&self, _: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::Integer(self.0 as i32)))
+    }
+}
+
+/// `\mathchardef`, which defines a control sequence to stand for a math
+/// character code.
+#[derive(Clone, Debug)]
+pub struct MathCharDef;
+
+impl Macro for MathCharDef {
+    fn name(&self) -> String {
+        r"\mathchardef".to_string()
+    }
+}
+
+impl Unexpandable for MathCharDef {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        let name = get_r_token(state)?;
+        let global = state.prefixes.global;
+        state.define(name, Some(Command::Unexpandable(Box::new(Relax))));
+        state.prefixes.global = global;
+        state.scan_optional_equals()?;
+        let mut n = state.scan_int()?;
+        if !(0..0x8000).contains(&n) {
+            state.recoverable_error(Error::new(
+                ErrorKind::BadNumber,
+                format!("Bad mathchar ({})", n),
+            ))?;
+            n = 0;
+        }
+        state.define(
+            name,
+            Some(Command::Unexpandable(Box::new(MathGiven(n as u16)))),
+        );
+        Ok(())
+    }
+
+    fn prefixing(&self) -> Prefixing {
+        Prefixing::Assignment
+    }
+}
+
+/// A control sequence defined by `\mathchardef`. It stands for its math
+/// character code when used as a number.
+#[derive(Clone, Debug)]
+pub struct MathGiven(pub u16);
+
+impl Macro for MathGiven {
+    fn name(&self) -> String {
+        format!("\\mathchar\"{:X}", self.0)
+    }
+}
+
+impl Unexpandable for MathGiven {
+    fn execute(&self, state: &mut TexState) -> Result<(), Error> {
+        if state.mode.is_math() {
+            // There are no math lists to append the character to yet.
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::MissingDollar,
+                "Missing $ inserted".to_string(),
+            ))
+        }
+    }
+
+    fn value(&self, _: &mut TexState) -> Result<Option<Value>, Error> {
+        Ok(Some(Value::Integer(self.0 as i32)))
     }
 }
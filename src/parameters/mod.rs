@@ -0,0 +1,111 @@
+use crate::scanning::{arithmetic::Glue, Value};
+
+/// TeX's integer parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntegerParameter {
+    EndLineChar,
+    EscapeChar,
+    GlobalDefs,
+    Mag,
+    TracingMacros,
+    TracingOnline,
+}
+
+impl IntegerParameter {
+    pub const ALL: [IntegerParameter; 6] = [
+        IntegerParameter::EndLineChar,
+        IntegerParameter::EscapeChar,
+        IntegerParameter::GlobalDefs,
+        IntegerParameter::Mag,
+        IntegerParameter::TracingMacros,
+        IntegerParameter::TracingOnline,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegerParameter::EndLineChar => r"\endlinechar",
+            IntegerParameter::EscapeChar => r"\escapechar",
+            IntegerParameter::GlobalDefs => r"\globaldefs",
+            IntegerParameter::Mag => r"\mag",
+            IntegerParameter::TracingMacros => r"\tracingmacros",
+            IntegerParameter::TracingOnline => r"\tracingonline",
+        }
+    }
+    /// The value of the parameter before anything has been assigned to it.
+    pub fn default_value(&self) -> i32 {
+        match self {
+            IntegerParameter::EndLineChar => '\r' as i32,
+            IntegerParameter::EscapeChar => '\\' as i32,
+            IntegerParameter::GlobalDefs => 0,
+            IntegerParameter::Mag => 1000,
+            IntegerParameter::TracingMacros | IntegerParameter::TracingOnline => 0,
+        }
+    }
+}
+
+/// TeX's token list parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenParameter {
+    ErrHelp,
+}
+
+impl TokenParameter {
+    pub const ALL: [TokenParameter; 1] = [TokenParameter::ErrHelp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenParameter::ErrHelp => r"\errhelp",
+        }
+    }
+}
+
+/// TeX's register banks, whose registers are selected by number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterKind {
+    Count,
+    Dimen,
+    Skip,
+    MuSkip,
+    Toks,
+}
+
+impl RegisterKind {
+    pub const ALL: [RegisterKind; 5] = [
+        RegisterKind::Count,
+        RegisterKind::Dimen,
+        RegisterKind::Skip,
+        RegisterKind::MuSkip,
+        RegisterKind::Toks,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RegisterKind::Count => r"\count",
+            RegisterKind::Dimen => r"\dimen",
+            RegisterKind::Skip => r"\skip",
+            RegisterKind::MuSkip => r"\muskip",
+            RegisterKind::Toks => r"\toks",
+        }
+    }
+    /// The command that defines a control sequence for a register of this
+    /// kind, e.g. `\countdef`.
+    pub fn def_name(&self) -> &'static str {
+        match self {
+            RegisterKind::Count => r"\countdef",
+            RegisterKind::Dimen => r"\dimendef",
+            RegisterKind::Skip => r"\skipdef",
+            RegisterKind::MuSkip => r"\muskipdef",
+            RegisterKind::Toks => r"\toksdef",
+        }
+    }
+    /// The value of a register before anything has been assigned to it.
+    pub fn default_value(&self) -> Value {
+        match self {
+            RegisterKind::Count => Value::Integer(0),
+            RegisterKind::Dimen => Value::Dimension(0),
+            RegisterKind::Skip => Value::Glue(Glue::default()),
+            RegisterKind::MuSkip => Value::MuGlue(Glue::default()),
+            RegisterKind::Toks => Value::Tokens(vec![]),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use super::{lexer::TexFile, parser::Token, symbol::Symbol};
+use crate::{
+    constants,
+    errors::{Error, ErrorKind},
+};
+
+/// Where the tokens of a token list level came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenListKind {
+    /// The replacement text of the named macro.
+    Macro(Symbol),
+    /// Tokens that were read and then put back onto the input.
+    BackedUp,
+    /// Tokens inserted by the engine itself, e.g. during error recovery.
+    Inserted,
+    /// A token following `\noexpand`, which is not expanded when it is read.
+    NoExpand,
+}
+
+/// A token list that is being read as part of the input.
+#[derive(Debug)]
+pub struct TokenList {
+    kind: TokenListKind,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl TokenList {
+    pub fn new(kind: TokenListKind, tokens: Vec<Token>) -> Self {
+        Self {
+            kind,
+            tokens,
+            position: 0,
+        }
+    }
+    pub fn kind(&self) -> &TokenListKind {
+        &self.kind
+    }
+    pub fn is_exhausted(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+    /// The tokens that have already been read from this list.
+    pub fn read(&self) -> &[Token] {
+        &self.tokens[..self.position]
+    }
+    /// The tokens that are still to be read from this list.
+    pub fn remaining(&self) -> &[Token] {
+        &self.tokens[self.position..]
+    }
+    pub fn next_token(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.position).copied();
+        if t.is_some() {
+            self.position += 1;
+        }
+        t
+    }
+}
+
+/// One level of the input stack.
+#[derive(Debug)]
+pub enum InputLevel {
+    File(TexFile),
+    TokenList(TokenList),
+}
+
+/// TeX's input stack: file levels interleaved with token list levels, the
+/// topmost of which is the current source of input.
+#[derive(Debug, Default)]
+pub struct InputStack {
+    levels: Vec<InputLevel>,
+}
+
+impl InputStack {
+    pub fn new() -> Self {
+        Self { levels: vec![] }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+    pub fn levels(&self) -> &[InputLevel] {
+        &self.levels
+    }
+    pub fn current(&mut self) -> Option<&mut InputLevel> {
+        self.levels.last_mut()
+    }
+    /// The topmost level if it is a file.
+    pub fn current_file(&mut self) -> Option<&mut TexFile> {
+        match self.levels.last_mut() {
+            Some(InputLevel::File(f)) => Some(f),
+            _ => None,
+        }
+    }
+    /// The innermost file being read, even if token lists are above it.
+    pub fn innermost_file(&mut self) -> Option<&mut TexFile> {
+        self.levels.iter_mut().rev().find_map(|l| match l {
+            InputLevel::File(f) => Some(f),
+            _ => None,
+        })
+    }
+    /// The number of the current line of the innermost file being read, or 0
+    /// if no file is open.
+    pub fn line(&self) -> usize {
+        self.levels
+            .iter()
+            .rev()
+            .find_map(|l| match l {
+                InputLevel::File(f) => Some(f.get_text_position().0),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+    pub fn pop(&mut self) -> Option<InputLevel> {
+        self.levels.pop()
+    }
+    /// Removes token lists at the top of the stack that have been read
+    /// completely.
+    pub fn cleanup(&mut self) {
+        while let Some(InputLevel::TokenList(l)) = self.levels.last() {
+            if !l.is_exhausted() {
+                break;
+            }
+            self.levels.pop();
+        }
+    }
+    fn push(&mut self, level: InputLevel) -> Result<(), Error> {
+        self.cleanup();
+        if self.levels.len() >= constants::STACK_SIZE {
+            return Err(Error::new(
+                ErrorKind::Overflow,
+                format!(
+                    "TeX capacity exceeded, sorry [input stack size={}]",
+                    constants::STACK_SIZE
+                ),
+            ));
+        }
+        self.levels.push(level);
+        Ok(())
+    }
+    pub fn push_file(&mut self, file: TexFile) -> Result<(), Error> {
+        let open = self
+            .levels
+            .iter()
+            .filter(|l| matches!(l, InputLevel::File(_)))
+            .count();
+        if open >= constants::MAX_IN_OPEN {
+            return Err(Error::new(
+                ErrorKind::Overflow,
+                format!(
+                    "TeX capacity exceeded, sorry [text input levels={}]",
+                    constants::MAX_IN_OPEN
+                ),
+            ));
+        }
+        self.push(InputLevel::File(file))
+    }
+    pub fn push_tokens(&mut self, kind: TokenListKind, tokens: Vec<Token>) -> Result<(), Error> {
+        self.push(InputLevel::TokenList(TokenList::new(kind, tokens)))
+    }
+    /// Puts a single token back onto the input.
+    pub fn back_input(&mut self, token: Token) -> Result<(), Error> {
+        self.push_tokens(TokenListKind::BackedUp, vec![token])
+    }
+}
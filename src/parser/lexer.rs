@@ -1,4 +1,10 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{collections::HashMap, fmt::Debug, io::Cursor, path::PathBuf};
+
+use super::{parser::Token, symbol::Symbol};
+use crate::{
+    errors::{Error, ErrorKind},
+    parsing::Input,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CharacterCategory {
@@ -20,55 +26,395 @@ pub enum CharacterCategory {
     Invalid,
 }
 
+/// The state of the input line reader, as in TeX: at the beginning of a
+/// line, in the middle of a line, or skipping blanks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineState {
+    NewLine,
+    MidLine,
+    SkipBlanks,
+}
+
+impl CharacterCategory {
+    const ALL: [CharacterCategory; 16] = [
+        CharacterCategory::Escape,
+        CharacterCategory::BeginGroup,
+        CharacterCategory::EndGroup,
+        CharacterCategory::MathShift,
+        CharacterCategory::AlignmentTab,
+        CharacterCategory::EndOfLine,
+        CharacterCategory::Parameter,
+        CharacterCategory::Superscript,
+        CharacterCategory::Subscript,
+        CharacterCategory::Ignored,
+        CharacterCategory::Space,
+        CharacterCategory::Letter,
+        CharacterCategory::Other,
+        CharacterCategory::Active,
+        CharacterCategory::Comment,
+        CharacterCategory::Invalid,
+    ];
+    /// The category with the given TeX category code.
+    pub fn from_code(code: i32) -> Option<Self> {
+        usize::try_from(code)
+            .ok()
+            .and_then(|i| Self::ALL.get(i).copied())
+    }
+    /// The TeX category code of this category.
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
 pub struct TexFile {
     file_name: String,
     path: String,
-    contents: String,
-    position: usize,
+    input: Input,
+    finished: bool,
+    /// The current line, without trailing spaces and with the end line
+    /// character appended.
+    buffer: Vec<char>,
+    /// How many characters and bytes of the file each character of `buffer`
+    /// was read from: more than one for a reduced `^^` sequence.
+    widths: Vec<(usize, usize)>,
+    /// Index of the next character of `buffer` to be read.
+    loc: usize,
+    /// The byte offset in the file at which the current line starts.
+    line_start: usize,
+    line: usize,
+    state: LineState,
 }
 
 impl TexFile {
-    pub fn new(path: String) -> Self {
-        let file_name = path.split("/").last().unwrap().to_string();
-
-        let contents = std::fs::read_to_string(&path).unwrap();
-        Self {
-            file_name,
-            path,
-            contents,
-            position: 0,
+    /// Opens the file `name`, looking for it as described for
+    /// [`TexFile::candidates`]. If it cannot be found, the error lists the
+    /// paths that were tried.
+    pub fn open(name: &str, search_path: &[PathBuf]) -> Result<Self, Error> {
+        let tried = Self::candidates(name, search_path);
+        for candidate in &tried {
+            match std::fs::File::open(candidate) {
+                Ok(file) => {
+                    let file_name = candidate
+                        .file_name()
+                        .map_or(name.to_string(), |n| n.to_string_lossy().into_owned());
+                    let mut s = Self::new_from_input(Input::new_from_file(&file_name, file));
+                    s.path = candidate.display().to_string();
+                    return Ok(s);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    return Err(Error::new(
+                        ErrorKind::IoError,
+                        format!("I can't open file `{}': {}", candidate.display(), e),
+                    ))
+                }
+            }
         }
+        let tried: Vec<_> = tried.iter().map(|p| p.display().to_string()).collect();
+        Err(Error::new(
+            ErrorKind::FileNotFound,
+            format!("I can't find file `{}' (tried {})", name, tried.join(", ")),
+        ))
     }
-    pub fn new_from_contents(name: String, contents: String) -> Self {
+    /// The paths at which the file `name` is looked for, in order: relative
+    /// to the current directory and then to each directory of `search_path`,
+    /// trying `name` with `.tex` appended first if it has no extension.
+    /// Absolute names are not searched for.
+    pub fn candidates(name: &str, search_path: &[PathBuf]) -> Vec<PathBuf> {
+        let mut names = vec![PathBuf::from(name)];
+        if names[0].extension().is_none() {
+            names.insert(0, PathBuf::from(format!("{}.tex", name)));
+        }
+        if names[0].is_absolute() {
+            return names;
+        }
+        let mut candidates = names.clone();
+        for dir in search_path {
+            candidates.extend(names.iter().map(|n| dir.join(n)));
+        }
+        candidates
+    }
+    pub fn new_from_input(input: Input) -> Self {
         Self {
-            file_name: name,
-            path: "custom".to_string(),
-            contents,
-            position: 0,
+            file_name: input.name.clone(),
+            path: input.name.clone(),
+            input,
+            finished: false,
+            buffer: vec![],
+            widths: vec![],
+            loc: 0,
+            line_start: 0,
+            line: 0,
+            state: LineState::NewLine,
         }
     }
+    pub fn new_from_contents(name: String, contents: String) -> Self {
+        let mut s = Self::new_from_input(Input::new_from_source(
+            &name,
+            Box::new(Cursor::new(contents.into_bytes())),
+        ));
+        s.path = "custom".to_string();
+        s
+    }
+    pub fn name(&self) -> &str {
+        &self.file_name
+    }
+    /// The path at which the file was opened.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Makes the file end after the current line, as `\endinput` does.
+    pub fn end_input(&mut self) {
+        self.finished = true;
+    }
+    /// The line and column of the next character to be read, both counted
+    /// from 1. Columns count characters, not bytes.
     pub fn get_text_position(&self) -> (usize, usize) {
-        let (care, _) = self.contents.split_at(self.position);
-        let mut line_nr = 1;
-        let mut last_new_line = 0;
-        for (i, c) in care.chars().enumerate() {
-            if c == '\n' {
-                line_nr += 1;
-                last_new_line = i;
+        let end = self.loc.min(self.widths.len());
+        (
+            self.line,
+            1 + self.widths[..end].iter().map(|w| w.0).sum::<usize>(),
+        )
+    }
+    /// The byte offset in the file of the next character to be read.
+    pub fn byte_offset(&self) -> usize {
+        let end = self.loc.min(self.widths.len());
+        self.line_start + self.widths[..end].iter().map(|w| w.1).sum::<usize>()
+    }
+    /// The current line split into the part that has been read and the
+    /// rest, leaving out the end line character `end_line_char` at its end.
+    pub fn context(&self, end_line_char: i32) -> (String, String) {
+        let mut end = self.buffer.len();
+        if self
+            .buffer
+            .last()
+            .is_some_and(|&c| c as i32 == end_line_char)
+        {
+            end -= 1;
+        }
+        let loc = self.loc.min(end);
+        (
+            self.buffer[..loc].iter().collect(),
+            self.buffer[loc..end].iter().collect(),
+        )
+    }
+    pub fn state(&self) -> LineState {
+        self.state
+    }
+
+    /// Reads the next line into the buffer, removing trailing blanks and
+    /// appending `end_line_char` unless it is negative. Returns `false` at the
+    /// end of the file. Like web2c rather than TeX82, trailing tabs and the
+    /// carriage return of a CRLF line ending count as blanks. If the line
+    /// cannot be decoded, it is still counted and its valid part is kept in
+    /// the buffer before the error is returned, so that reading can go on
+    /// after it.
+    fn read_line(&mut self, end_line_char: i32) -> Result<bool, Error> {
+        if self.finished {
+            return Ok(false);
+        }
+        self.buffer.clear();
+        self.widths.clear();
+        self.loc = 0;
+        self.line_start = self.input.pos;
+        let (read, error) = match self.input.read_line(&mut self.buffer) {
+            Ok(read) => (read, None),
+            Err(e) => (true, Some(e)),
+        };
+        if !read {
+            self.finished = true;
+            return Ok(false);
+        }
+        let column = self.buffer.len() + 1;
+        while let Some(' ' | '\t' | '\r') = self.buffer.last() {
+            self.buffer.pop();
+        }
+        if end_line_char >= 0 {
+            if let Some(c) = char::from_u32(end_line_char as u32) {
+                self.buffer.push(c);
             }
         }
+        self.widths = self.buffer.iter().map(|c| (1, c.len_utf8())).collect();
+        self.line += 1;
+        self.state = LineState::NewLine;
+        if let Some(e) = error {
+            let kind = match e.kind() {
+                std::io::ErrorKind::InvalidData => ErrorKind::InvalidUtf8,
+                _ => ErrorKind::IoError,
+            };
+            return Err(Error::new_with_location(
+                Some((self.file_name.clone(), self.line, column)),
+                kind,
+                format!("Could not read {}: {}", self.file_name, e),
+            ));
+        }
+        Ok(true)
+    }
 
-        (line_nr, care.len() - last_new_line)
+    /// Decodes a `^^` sequence whose first superscript character `c` has
+    /// already been read and whose remainder starts at `loc`. Returns the
+    /// resulting character and the number of characters taken from `loc` on.
+    ///
+    /// Besides TeX's `^^xx` (two lowercase hexadecimal digits) and `^^c`
+    /// forms, the `^^^^xxxx` and `^^^^^^xxxxxx` forms of the Unicode engines
+    /// are recognized.
+    fn decode_superscripts(&self, c: char, loc: usize) -> Option<(char, usize)> {
+        let buffer = &self.buffer;
+        if buffer.get(loc) != Some(&c) {
+            return None;
+        }
+        let repeated = |n: usize| (loc..loc + n).all(|i| buffer.get(i) == Some(&c));
+        let hex = |from: usize, n: usize| -> Option<char> {
+            if from + n > buffer.len() {
+                return None;
+            }
+            let mut value = 0u32;
+            for d in &buffer[from..from + n] {
+                match d {
+                    '0'..='9' | 'a'..='f' => value = value * 16 + d.to_digit(16).unwrap(),
+                    _ => return None,
+                }
+            }
+            char::from_u32(value)
+        };
+        if repeated(5) {
+            if let Some(d) = hex(loc + 5, 6) {
+                return Some((d, 11));
+            }
+        }
+        if repeated(3) {
+            if let Some(d) = hex(loc + 3, 4) {
+                return Some((d, 7));
+            }
+        }
+        if let Some(d) = hex(loc + 1, 2) {
+            return Some((d, 3));
+        }
+        match buffer.get(loc + 1) {
+            Some(&d) if (d as u32) < 0o100 => Some((char::from_u32(d as u32 + 0o100)?, 2)),
+            Some(&d) if (d as u32) < 0o200 => Some((char::from_u32(d as u32 - 0o100)?, 2)),
+            _ => None,
+        }
     }
-    pub fn get_current_char(&self, offset: usize) -> Option<char> {
-        self.contents.chars().nth(self.position + offset)
+
+    /// Replaces a `^^` sequence starting at `loc` in the buffer by the
+    /// character it denotes, as TeX does inside control sequence names.
+    /// Returns `true` if anything was reduced.
+    fn reduce_superscripts(
+        &mut self,
+        loc: usize,
+        category: &dyn Fn(char) -> CharacterCategory,
+    ) -> bool {
+        let c = match self.buffer.get(loc) {
+            Some(&c) if category(c) == CharacterCategory::Superscript => c,
+            _ => return false,
+        };
+        match self.decode_superscripts(c, loc + 1) {
+            Some((d, n)) => {
+                let width = self.widths[loc..loc + 1 + n]
+                    .iter()
+                    .fold((0, 0), |(c, b), w| (c + w.0, b + w.1));
+                self.buffer.splice(loc..loc + 1 + n, [d]);
+                self.widths.splice(loc..loc + 1 + n, [width]);
+                true
+            }
+            None => false,
+        }
     }
-    pub fn advance(&mut self, offset: usize) -> Result<(), ()> {
-        if self.contents.len() >= self.position + offset {
-            self.position += offset;
-            Ok(())
-        } else {
-            Err(())
+
+    /// Scans the name of a control sequence whose escape character has just
+    /// been read.
+    fn scan_control_sequence(&mut self, category: &dyn Fn(char) -> CharacterCategory) -> Token {
+        let mut command = String::from("\\");
+        while self.reduce_superscripts(self.loc, category) {}
+        if self.loc >= self.buffer.len() {
+            return Token::ControlSequence(Symbol::intern(&command));
+        }
+        let c = self.buffer[self.loc];
+        self.loc += 1;
+        command.push(c);
+        match category(c) {
+            CharacterCategory::Letter => {
+                loop {
+                    while self.reduce_superscripts(self.loc, category) {}
+                    match self.buffer.get(self.loc) {
+                        Some(&c) if category(c) == CharacterCategory::Letter => {
+                            command.push(c);
+                            self.loc += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                self.state = LineState::SkipBlanks;
+            }
+            CharacterCategory::Space => self.state = LineState::SkipBlanks,
+            _ => self.state = LineState::MidLine,
+        }
+        Token::ControlSequence(Symbol::intern(&command))
+    }
+
+    /// Reads the next token from this file, using `category` to look up
+    /// category codes. Returns `None` once the file is exhausted.
+    pub fn next_token(
+        &mut self,
+        category: &dyn Fn(char) -> CharacterCategory,
+        end_line_char: i32,
+    ) -> Result<Option<Token>, Error> {
+        loop {
+            if self.loc >= self.buffer.len() && !self.read_line(end_line_char)? {
+                return Ok(None);
+            }
+            let mut c = self.buffer[self.loc];
+            self.loc += 1;
+            let mut cat = category(c);
+            while cat == CharacterCategory::Superscript {
+                match self.decode_superscripts(c, self.loc) {
+                    Some((d, n)) => {
+                        c = d;
+                        self.loc += n;
+                        cat = category(c);
+                    }
+                    None => break,
+                }
+            }
+            let token = match cat {
+                CharacterCategory::Escape => self.scan_control_sequence(category),
+                CharacterCategory::Active => {
+                    self.state = LineState::MidLine;
+                    Token::ControlSequence(Symbol::active(c))
+                }
+                CharacterCategory::Space => match self.state {
+                    LineState::MidLine => {
+                        self.state = LineState::SkipBlanks;
+                        Token::Character(' ', CharacterCategory::Space)
+                    }
+                    _ => continue,
+                },
+                CharacterCategory::EndOfLine => {
+                    self.loc = self.buffer.len();
+                    match self.state {
+                        LineState::NewLine => Token::ControlSequence(Symbol::intern(r"\par")),
+                        LineState::MidLine => Token::Character(' ', CharacterCategory::Space),
+                        LineState::SkipBlanks => continue,
+                    }
+                }
+                CharacterCategory::Comment => {
+                    self.loc = self.buffer.len();
+                    continue;
+                }
+                CharacterCategory::Ignored => continue,
+                CharacterCategory::Invalid => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidCharacter,
+                        "Text line contains an invalid character".to_string(),
+                    ))
+                }
+                cat => {
+                    self.state = LineState::MidLine;
+                    Token::Character(c, cat)
+                }
+            };
+            return Ok(Some(token));
         }
     }
 }
@@ -78,7 +424,9 @@ impl Debug for TexFile {
         f.debug_struct("File")
             .field("file_name", &self.file_name)
             .field("path", &self.path)
-            .field("position", &self.position)
+            .field("line", &self.line)
+            .field("loc", &self.loc)
+            .field("state", &self.state)
             .finish()
     }
 }
@@ -86,6 +434,14 @@ impl Debug for TexFile {
 #[derive(Debug, Clone)]
 pub struct CharacterMap(HashMap<char, CharacterCategory>);
 
+impl IntoIterator for CharacterMap {
+    type Item = (char, CharacterCategory);
+    type IntoIter = std::collections::hash_map::IntoIter<char, CharacterCategory>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
 impl CharacterMap {
     pub fn new() -> Self {
         let map = HashMap::new();
@@ -107,12 +463,13 @@ impl CharacterMap {
                     '}' => CharacterCategory::EndGroup,
                     '$' => CharacterCategory::MathShift,
                     '&' => CharacterCategory::AlignmentTab,
-                    '\n' => CharacterCategory::EndOfLine,
+                    '\n' | '\r' => CharacterCategory::EndOfLine,
                     '#' => CharacterCategory::Parameter,
                     '^' => CharacterCategory::Superscript,
                     '_' => CharacterCategory::Subscript,
                     '\0' => CharacterCategory::Ignored,
-                    ' ' => CharacterCategory::Space,
+                    // Unlike INITEX, but like plain TeX, a tab is a space.
+                    ' ' | '\t' => CharacterCategory::Space,
                     'A'..='Z' | 'a'..='z' => CharacterCategory::Letter,
                     '~' => CharacterCategory::Active,
                     '%' => CharacterCategory::Comment,
@@ -125,6 +482,9 @@ impl CharacterMap {
     pub fn set(&mut self, chr: char, cat: CharacterCategory) {
         self.0.insert(chr, cat);
     }
+    pub fn remove(&mut self, chr: char) {
+        self.0.remove(&chr);
+    }
     pub fn get(&self, chr: char) -> Option<CharacterCategory> {
         match self.0.get(&chr) {
             Some(a) => Some(a.clone()),
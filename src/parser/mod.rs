@@ -1,2 +1,4 @@
+pub mod input_stack;
 pub mod lexer;
 pub mod parser;
+pub mod symbol;
@@ -1,10 +1,11 @@
 
 use super::lexer::CharacterCategory;
+use super::symbol::Symbol;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Token {
     Character(char, CharacterCategory),
-    ControlSequence(String),
+    ControlSequence(Symbol),
     Parameter(char, u8),
 }
 
@@ -12,7 +13,7 @@ impl ToString for Token {
     fn to_string(&self) -> String {
         match self {
             Token::Character(a, _) => a.to_string(),
-            Token::ControlSequence(s) => s.clone(),
+            Token::ControlSequence(s) => s.name().to_string(),
             Token::Parameter(c, u) => format!("{c}{u}"),
         }
     }
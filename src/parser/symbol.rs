@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::sync::{Mutex, OnceLock};
+
+/// The name of a control sequence, interned in a process-wide table so that
+/// it can be copied, compared and hashed as a single integer. Names keep
+/// their leading backslash; active characters are stored as the bare
+/// character but apart from the names, so that the active `\` and the empty
+/// name `\csname\endcsname` are different, as TeX's `active_base` and
+/// `null_cs` are.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The names of all symbols made so far, like TeX's string pool.
+#[derive(Default)]
+struct SymbolTable {
+    names: Vec<(&'static str, bool)>,
+    symbols: HashMap<(&'static str, bool), Symbol>,
+    characters: usize,
+}
+
+fn table() -> &'static Mutex<SymbolTable> {
+    static TABLE: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
+    TABLE.get_or_init(Default::default)
+}
+
+impl Symbol {
+    /// The symbol for the control sequence `name`, which is added to the
+    /// table if it is new.
+    pub fn intern(name: &str) -> Symbol {
+        Self::lookup(name, false)
+    }
+
+    /// The symbol for the active character `c`.
+    pub fn active(c: char) -> Symbol {
+        Self::lookup(c.encode_utf8(&mut [0; 4]), true)
+    }
+
+    fn lookup(name: &str, active: bool) -> Symbol {
+        let mut table = table().lock().unwrap();
+        if let Some(&symbol) = table.symbols.get(&(name, active)) {
+            return symbol;
+        }
+        // Names live as long as the table does, so they are never freed.
+        let name: &'static str = Box::leak(name.into());
+        let symbol = Symbol(table.names.len() as u32);
+        table.names.push((name, active));
+        table.symbols.insert((name, active), symbol);
+        table.characters += name.len();
+        symbol
+    }
+
+    pub fn name(self) -> &'static str {
+        table().lock().unwrap().names[self.0 as usize].0
+    }
+
+    /// Whether this is an active character rather than a control sequence.
+    pub fn is_active(self) -> bool {
+        table().lock().unwrap().names[self.0 as usize].1
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.name())
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How much of the symbol table is in use: the number of names and their
+/// total length in bytes.
+pub fn usage() -> (usize, usize) {
+    let table = table().lock().unwrap();
+    (table.names.len(), table.characters)
+}
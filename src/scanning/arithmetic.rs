@@ -0,0 +1,172 @@
+use std::fmt::Display;
+
+/// A dimension in scaled points (`sp`), i.e. units of `2^-16 pt`.
+pub type Scaled = i32;
+
+/// One point, `2^16 sp`.
+pub const UNITY: Scaled = 0x10000;
+/// The largest legal dimension, `2^30 - 1 sp`.
+pub const MAX_DIMEN: Scaled = 0o7777777777;
+
+/// The order of infinity of a stretch or shrink component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum GlueOrder {
+    #[default]
+    Normal,
+    Fil,
+    Fill,
+    Filll,
+}
+
+impl GlueOrder {
+    pub fn next(self) -> Option<Self> {
+        match self {
+            GlueOrder::Normal => Some(GlueOrder::Fil),
+            GlueOrder::Fil => Some(GlueOrder::Fill),
+            GlueOrder::Fill => Some(GlueOrder::Filll),
+            GlueOrder::Filll => None,
+        }
+    }
+}
+
+/// A glue specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Glue {
+    pub width: Scaled,
+    pub stretch: Scaled,
+    pub stretch_order: GlueOrder,
+    pub shrink: Scaled,
+    pub shrink_order: GlueOrder,
+}
+
+/// Raised when a computation does not fit into TeX's ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticOverflow;
+
+/// Converts the decimal fraction `.d_0 d_1 ... d_k` into scaled units,
+/// rounding exactly the way TeX does.
+pub fn round_decimals(digits: &[u8]) -> Scaled {
+    let mut a: Scaled = 0;
+    for &d in digits.iter().rev() {
+        a = (a + d as Scaled * 0o400000) / 10;
+    }
+    (a + 1) / 2
+}
+
+/// Computes `x * n / d` truncated towards zero, together with the remainder,
+/// failing if the result is `2^30` or more in absolute value.
+pub fn xn_over_d(x: Scaled, n: i32, d: i32) -> Result<(Scaled, Scaled), ArithmeticOverflow> {
+    let p = x as i64 * n as i64;
+    let (q, r) = (p / d as i64, p % d as i64);
+    if q.abs() >= 0o10000000000 {
+        Err(ArithmeticOverflow)
+    } else {
+        Ok((q as Scaled, r as Scaled))
+    }
+}
+
+/// Computes `n * x + y`, failing if the absolute value of the result exceeds
+/// `max_answer`.
+pub fn mult_and_add(
+    n: i32,
+    x: Scaled,
+    y: Scaled,
+    max_answer: Scaled,
+) -> Result<Scaled, ArithmeticOverflow> {
+    let r = n as i64 * x as i64 + y as i64;
+    if r.abs() > max_answer as i64 {
+        Err(ArithmeticOverflow)
+    } else {
+        Ok(r as Scaled)
+    }
+}
+
+/// Computes `n * x + y` for dimensions.
+pub fn nx_plus_y(n: i32, x: Scaled, y: Scaled) -> Result<Scaled, ArithmeticOverflow> {
+    mult_and_add(n, x, y, MAX_DIMEN)
+}
+
+/// Divides `x` by `n`, truncating towards zero.
+pub fn x_over_n(x: Scaled, n: i32) -> Result<Scaled, ArithmeticOverflow> {
+    if n == 0 {
+        Err(ArithmeticOverflow)
+    } else {
+        Ok((x as i64 / n as i64) as Scaled)
+    }
+}
+
+/// Formats a dimension in points the way TeX's `print_scaled` does, with the
+/// fewest decimal digits that read back as the same value.
+pub fn format_scaled(s: Scaled) -> String {
+    let mut out = String::new();
+    let mut s = s as i64;
+    if s < 0 {
+        out.push('-');
+        s = -s;
+    }
+    out.push_str(&(s / UNITY as i64).to_string());
+    out.push('.');
+    let mut s = 10 * (s % UNITY as i64) + 5;
+    let mut delta = 10;
+    loop {
+        if delta > UNITY as i64 {
+            // Round the last digit.
+            s += 0o100000 - 50000;
+        }
+        out.push(char::from_digit((s / UNITY as i64) as u32, 10).unwrap());
+        s = 10 * (s % UNITY as i64);
+        delta *= 10;
+        if s <= delta {
+            break;
+        }
+    }
+    out
+}
+
+impl Display for GlueOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GlueOrder::Normal => "",
+            GlueOrder::Fil => "fil",
+            GlueOrder::Fill => "fill",
+            GlueOrder::Filll => "filll",
+        })
+    }
+}
+
+/// Formats a glue component, e.g. `2.0pt` or `1.0fil`.
+fn format_component(s: Scaled, order: GlueOrder, unit: &str) -> String {
+    match order {
+        GlueOrder::Normal => format!("{}{}", format_scaled(s), unit),
+        order => format!("{}{}", format_scaled(s), order),
+    }
+}
+
+impl Glue {
+    /// Formats the glue as TeX's `print_spec` does, with `unit` being `pt` or
+    /// `mu`.
+    pub fn format(&self, unit: &str) -> String {
+        let mut out = format_component(self.width, GlueOrder::Normal, unit);
+        if self.stretch != 0 {
+            out.push_str(" plus ");
+            out.push_str(&format_component(self.stretch, self.stretch_order, unit));
+        }
+        if self.shrink != 0 {
+            out.push_str(" minus ");
+            out.push_str(&format_component(self.shrink, self.shrink_order, unit));
+        }
+        out
+    }
+}
+
+impl std::ops::Neg for Glue {
+    type Output = Glue;
+    fn neg(self) -> Glue {
+        Glue {
+            width: -self.width,
+            stretch: -self.stretch,
+            shrink: -self.shrink,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,651 @@
+use crate::{
+    constants,
+    errors::{Error, ErrorKind},
+    macros::Command,
+    parameters::IntegerParameter,
+    parser::{input_stack::TokenListKind, lexer::CharacterCategory, parser::Token},
+    TexState,
+};
+
+pub mod arithmetic;
+use arithmetic::*;
+
+/// The value of an internal quantity, as produced by [`crate::macros::Macro::value`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i32),
+    Dimension(Scaled),
+    Glue(Glue),
+    MuGlue(Glue),
+    Tokens(Vec<Token>),
+}
+
+/// The largest integer TeX accepts, `2^31 - 1`.
+pub const INFINITY: i32 = i32::MAX;
+
+/// Converts text to tokens the way TeX's `str_toks` does: spaces become
+/// space tokens and all other characters have category 12.
+pub fn str_toks(s: &str) -> Vec<Token> {
+    s.chars()
+        .map(|c| match c {
+            ' ' => Token::Character(c, CharacterCategory::Space),
+            c => Token::Character(c, CharacterCategory::Other),
+        })
+        .collect()
+}
+
+fn is_other(t: &Token, c: char) -> bool {
+    *t == Token::Character(c, CharacterCategory::Other)
+}
+fn is_space(t: &Token) -> bool {
+    matches!(t, Token::Character(_, CharacterCategory::Space))
+}
+
+fn missing_number() -> Error {
+    Error::new(
+        ErrorKind::MissingNumber,
+        "Missing number, treated as zero".to_string(),
+    )
+}
+fn dimension_too_large() -> Error {
+    Error::new(
+        ErrorKind::DimensionTooLarge,
+        "Dimension too large".to_string(),
+    )
+}
+fn incompatible_glue_units() -> Error {
+    Error::new(
+        ErrorKind::IncompatibleGlueUnits,
+        "Incompatible glue units".to_string(),
+    )
+}
+fn illegal_unit(replacement: &str) -> Error {
+    Error::new(
+        ErrorKind::IllegalUnit,
+        format!("Illegal unit of measure ({} inserted)", replacement),
+    )
+}
+
+/// Physical units and their ratio to points.
+const UNITS: [(&str, i32, i32); 7] = [
+    ("in", 7227, 100),
+    ("pc", 12, 1),
+    ("cm", 7227, 254),
+    ("mm", 7227, 2540),
+    ("bp", 7227, 7200),
+    ("dd", 1238, 1157),
+    ("cc", 14856, 1157),
+];
+
+impl TexState {
+    /// Reads the next token, expanding expandable control sequences.
+    pub fn get_x_token(&mut self) -> Result<Token, Error> {
+        loop {
+            let (t, expand) = self.get_next()?;
+            if let (Token::ControlSequence(name), true) = (&t, expand) {
+                if let Some(Command::Expandable(m)) = self.state.get_macro(*name) {
+                    let m = m.clone();
+                    m.expand(self)?;
+                    continue;
+                }
+            }
+            return Ok(t);
+        }
+    }
+    /// Reads the next token, expanding expandable control sequences except
+    /// protected ones, as is done when token lists are fully expanded.
+    pub fn get_x_or_protected(&mut self) -> Result<Token, Error> {
+        loop {
+            let (t, expand) = self.get_next()?;
+            if let (Token::ControlSequence(name), true) = (&t, expand) {
+                if let Some(Command::Expandable(m)) = self.state.get_macro(*name) {
+                    let m = m.clone();
+                    if m.meaning() == r"\the" {
+                        // The result of `\the` is not expanded any further.
+                        let tokens = self.the_toks()?;
+                        self.push_tokens(TokenListKind::NoExpand, tokens)?;
+                        continue;
+                    } else if !m.protected() {
+                        m.expand(self)?;
+                        continue;
+                    }
+                }
+            }
+            return Ok(t);
+        }
+    }
+    /// Reads the next expanded token that is not a space.
+    pub fn get_x_non_blank(&mut self) -> Result<Token, Error> {
+        loop {
+            let t = self.get_x_token()?;
+            if !is_space(&t) {
+                return Ok(t);
+            }
+        }
+    }
+    /// Skips an optional space token.
+    pub fn scan_optional_space(&mut self) -> Result<(), Error> {
+        let t = self.get_x_token()?;
+        if !is_space(&t) {
+            self.back_input(t)?;
+        }
+        Ok(())
+    }
+    /// Skips spaces and an optional `=`.
+    pub fn scan_optional_equals(&mut self) -> Result<(), Error> {
+        let t = self.get_x_non_blank()?;
+        if !is_other(&t, '=') {
+            self.back_input(t)?;
+        }
+        Ok(())
+    }
+    /// Looks for the keyword `keyword` (given in lowercase) in the input,
+    /// ignoring the case of its letters and any spaces before it. If it is not
+    /// found, everything read is put back.
+    pub fn scan_keyword(&mut self, keyword: &str) -> Result<bool, Error> {
+        let mut read = Vec::new();
+        let mut expected = keyword.chars().peekable();
+        while let Some(&k) = expected.peek() {
+            let t = self.get_x_token()?;
+            match t {
+                Token::Character(c, cat)
+                    if cat != CharacterCategory::Active
+                        && (c == k || c == k.to_ascii_uppercase()) =>
+                {
+                    expected.next();
+                    read.push(t);
+                }
+                t if is_space(&t) && read.is_empty() => {}
+                t => {
+                    read.push(t);
+                    self.push_tokens(TokenListKind::BackedUp, read)?;
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+    /// Scans a file name: the expanded characters up to the next space or
+    /// non-character token, or the characters of a `{...}` group.
+    pub fn scan_file_name(&mut self) -> Result<String, Error> {
+        let mut name = String::new();
+        let t = self.get_x_non_blank()?;
+        if let Token::Character(_, CharacterCategory::BeginGroup) = t {
+            for t in self.scan_expanded_text()? {
+                if let Token::Character(c, _) = t {
+                    name.push(c);
+                }
+            }
+            return Ok(name);
+        }
+        let mut t = t;
+        loop {
+            match t {
+                Token::Character(c, cat) if cat != CharacterCategory::Space => name.push(c),
+                t if is_space(&t) => break,
+                t => {
+                    self.back_input(t)?;
+                    break;
+                }
+            }
+            t = self.get_x_token()?;
+        }
+        Ok(name)
+    }
+    /// Reads the value of the internal quantity `t`, if it is one.
+    fn scan_internal(&mut self, t: &Token) -> Result<Option<Value>, Error> {
+        if let Token::ControlSequence(name) = t {
+            if let Some(Command::Unexpandable(m)) = self.state.get_macro(*name) {
+                let m = m.clone();
+                return m.value(self);
+            }
+        }
+        Ok(None)
+    }
+    /// Reads the internal quantity after `\the` and returns its value as
+    /// tokens: a token list register as it is, and anything else as the
+    /// characters of its printed value.
+    pub fn the_toks(&mut self) -> Result<Vec<Token>, Error> {
+        let t = self.get_x_token()?;
+        let Some(value) = self.scan_internal(&t)? else {
+            return Err(Error::new(
+                ErrorKind::CantUseAfterThe,
+                format!(r"You can't use `{}' after \the", t.to_string()),
+            ));
+        };
+        Ok(match value {
+            Value::Integer(n) => str_toks(&n.to_string()),
+            Value::Dimension(d) => str_toks(&format!("{}pt", format_scaled(d))),
+            Value::Glue(g) => str_toks(&g.format("pt")),
+            Value::MuGlue(g) => str_toks(&g.format("mu")),
+            Value::Tokens(tokens) => tokens,
+        })
+    }
+    /// Reads optional signs, returning whether their product is negative and
+    /// the first token after them.
+    fn scan_signs(&mut self) -> Result<(bool, Token), Error> {
+        let mut negative = false;
+        loop {
+            let t = self.get_x_non_blank()?;
+            if is_other(&t, '-') {
+                negative = !negative;
+            } else if !is_other(&t, '+') {
+                return Ok((negative, t));
+            }
+        }
+    }
+    /// Scans an integer: an optionally signed decimal, octal (`'`),
+    /// hexadecimal (`"`) or alphabetic (`` ` ``) constant, or an internal
+    /// integer quantity.
+    pub fn scan_int(&mut self) -> Result<i32, Error> {
+        let (negative, t) = self.scan_signs()?;
+        let value = self.scan_unsigned_int(t)?;
+        Ok(if negative { -value } else { value })
+    }
+    /// Scans an integer without signs, starting with the token `t`.
+    fn scan_unsigned_int(&mut self, t: Token) -> Result<i32, Error> {
+        if is_other(&t, '`') {
+            let value = match self.get_element()? {
+                Token::Character(c, _) | Token::Parameter(c, _) => c as i32,
+                Token::ControlSequence(name) => {
+                    let mut chars = name.name().chars();
+                    if !name.is_active() {
+                        chars.next();
+                    }
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => c as i32,
+                        _ => {
+                            self.back_input(Token::ControlSequence(name))?;
+                            return Err(Error::new(
+                                ErrorKind::ImproperAlphabeticConstant,
+                                "Improper alphabetic constant".to_string(),
+                            ));
+                        }
+                    }
+                }
+            };
+            self.scan_optional_space()?;
+            return Ok(value);
+        }
+        if let Some(value) = self.scan_internal(&t)? {
+            return match value {
+                Value::Integer(i) | Value::Dimension(i) => Ok(i),
+                Value::Glue(g) => Ok(g.width),
+                Value::MuGlue(_) => Err(incompatible_glue_units()),
+                Value::Tokens(_) => Err(missing_number()),
+            };
+        }
+        let (value, _, t) = self.scan_constant(t)?;
+        if !is_space(&t) {
+            self.back_input(t)?;
+        }
+        Ok(value)
+    }
+    /// Scans the digits of a decimal, octal or hexadecimal constant starting
+    /// with `t`. Returns the value, the radix and the token that ended the
+    /// constant, which has not been put back.
+    fn scan_constant(&mut self, mut t: Token) -> Result<(i32, u32, Token), Error> {
+        let radix = if is_other(&t, '\'') {
+            8
+        } else if is_other(&t, '"') {
+            16
+        } else {
+            10
+        };
+        if radix != 10 {
+            t = self.get_x_token()?;
+        }
+        let mut value: i64 = 0;
+        let mut digits = 0;
+        let mut too_big = false;
+        loop {
+            let digit = match t {
+                Token::Character(c @ '0'..='9', CharacterCategory::Other) => c.to_digit(radix),
+                Token::Character(
+                    c @ 'A'..='F',
+                    CharacterCategory::Other | CharacterCategory::Letter,
+                ) if radix == 16 => c.to_digit(16),
+                _ => None,
+            };
+            let Some(digit) = digit else { break };
+            digits += 1;
+            value = value * radix as i64 + digit as i64;
+            // As in TeX, the remaining digits are read before complaining.
+            if value > INFINITY as i64 {
+                too_big = true;
+                value = INFINITY as i64;
+            }
+            t = self.get_x_token()?;
+        }
+        if digits == 0 {
+            self.back_input(t)?;
+            return Err(missing_number());
+        }
+        if too_big {
+            let e = Error::new(ErrorKind::NumberTooBig, "Number too big".to_string());
+            if let Err(e) = self.recoverable_error(e) {
+                self.back_input(t)?;
+                return Err(e);
+            }
+        }
+        Ok((value as i32, radix, t))
+    }
+    /// Scans an integer that has to be a valid character code.
+    pub fn scan_char_num(&mut self) -> Result<char, Error> {
+        let n = self.scan_int()?;
+        u32::try_from(n)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(Error::new(
+                ErrorKind::BadNumber,
+                format!("Bad character code ({})", n),
+            ))
+    }
+    /// Scans an integer that has to be a valid register number.
+    pub fn scan_register_num(&mut self) -> Result<u16, Error> {
+        let n = self.scan_int()?;
+        match u16::try_from(n) {
+            Ok(n) if (n as usize) < constants::NUMBER_OF_REGISTERS => Ok(n),
+            _ => Err(Error::new(
+                ErrorKind::BadNumber,
+                format!("Bad register code ({})", n),
+            )),
+        }
+    }
+
+    /// Scans the right-hand side of a token list assignment: either a
+    /// balanced text in braces or a token list register.
+    pub fn scan_token_list_value(&mut self) -> Result<Vec<Token>, Error> {
+        let t = self.get_x_non_blank()?;
+        if matches!(t, Token::Character(_, CharacterCategory::BeginGroup)) {
+            return self.scan_balanced_text();
+        }
+        match self.scan_internal(&t)? {
+            Some(Value::Tokens(tokens)) => Ok(tokens),
+            _ => {
+                self.back_input(t)?;
+                Err(Error::new(
+                    ErrorKind::MissingLeftBrace,
+                    "Missing { inserted".to_string(),
+                ))
+            }
+        }
+    }
+    /// Skips blanks and `\relax`, expanding, up to a `{` which is read.
+    pub fn scan_left_brace(&mut self) -> Result<(), Error> {
+        loop {
+            let t = self.get_x_non_blank()?;
+            match &t {
+                Token::Character(_, CharacterCategory::BeginGroup) => return Ok(()),
+                Token::ControlSequence(name)
+                    if matches!(
+                        self.state.get_macro(*name),
+                        Some(Command::Character(_, CharacterCategory::BeginGroup))
+                    ) =>
+                {
+                    return Ok(())
+                }
+                Token::ControlSequence(name)
+                    if self
+                        .state
+                        .get_macro(*name)
+                        .is_some_and(|m| m.meaning() == r"\relax") => {}
+                _ => {
+                    self.back_input(t)?;
+                    return Err(Error::new(
+                        ErrorKind::MissingLeftBrace,
+                        "Missing { inserted".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+    /// Scans a `{` and the balanced text after it, which is fully expanded
+    /// if `expand` is set.
+    pub fn scan_toks(&mut self, expand: bool) -> Result<Vec<Token>, Error> {
+        self.scan_left_brace()?;
+        if expand {
+            self.scan_expanded_text()
+        } else {
+            self.scan_balanced_text()
+        }
+    }
+    /// Reads the unexpanded tokens up to the `}` matching an already consumed
+    /// `{`.
+    pub fn scan_balanced_text(&mut self) -> Result<Vec<Token>, Error> {
+        self.scan_text(false)
+    }
+    /// Reads the tokens up to the `}` matching an already consumed `{`,
+    /// expanding everything except protected commands and tokens after
+    /// `\noexpand`.
+    pub fn scan_expanded_text(&mut self) -> Result<Vec<Token>, Error> {
+        self.scan_text(true)
+    }
+    fn scan_text(&mut self, expand: bool) -> Result<Vec<Token>, Error> {
+        let mut tokens = Vec::new();
+        let mut level = 0usize;
+        loop {
+            let t = if expand {
+                self.get_x_or_protected()?
+            } else {
+                self.get_element()?
+            };
+            match t {
+                Token::Character(_, CharacterCategory::BeginGroup) => level += 1,
+                Token::Character(_, CharacterCategory::EndGroup) => {
+                    if level == 0 {
+                        return Ok(tokens);
+                    }
+                    level -= 1;
+                }
+                _ => {}
+            }
+            tokens.push(t);
+        }
+    }
+
+    /// Scans a dimension such as `-1.5pt`, `3\dimen0` or `\hsize`.
+    pub fn scan_dimen(&mut self) -> Result<Scaled, Error> {
+        Ok(self.scan_dimension(false, false, None)?.0)
+    }
+    /// Scans a dimension in math units, such as `3mu`.
+    pub fn scan_mu_dimen(&mut self) -> Result<Scaled, Error> {
+        Ok(self.scan_dimension(true, false, None)?.0)
+    }
+    /// TeX's `scan_dimen`: `mu` selects math units, `inf` allows `fil`, `fill`
+    /// and `filll`, and `shortcut` is an integer that has already been scanned
+    /// (with its sign applied).
+    fn scan_dimension(
+        &mut self,
+        mu: bool,
+        inf: bool,
+        shortcut: Option<i32>,
+    ) -> Result<(Scaled, GlueOrder), Error> {
+        let mut negative = false;
+        let mut fraction: Scaled = 0;
+        let mut order = GlueOrder::Normal;
+        let mut value = match shortcut {
+            Some(value) => value,
+            None => {
+                let t;
+                (negative, t) = self.scan_signs()?;
+                match self.scan_internal(&t)? {
+                    Some(Value::Integer(i)) => i,
+                    Some(Value::Dimension(d)) if !mu => return self.attach_sign(d, negative),
+                    Some(Value::Glue(g)) if !mu => return self.attach_sign(g.width, negative),
+                    Some(Value::MuGlue(g)) if mu => return self.attach_sign(g.width, negative),
+                    Some(Value::Tokens(_)) => return Err(missing_number()),
+                    Some(_) => return Err(incompatible_glue_units()),
+                    None if is_other(&t, '.') || is_other(&t, ',') => {
+                        fraction = self.scan_decimals()?;
+                        0
+                    }
+                    None if is_other(&t, '`') => self.scan_unsigned_int(t)?,
+                    None => {
+                        let (value, radix, t) = self.scan_constant(t)?;
+                        if radix == 10 && (is_other(&t, '.') || is_other(&t, ',')) {
+                            fraction = self.scan_decimals()?;
+                        } else if !is_space(&t) {
+                            self.back_input(t)?;
+                        }
+                        value
+                    }
+                }
+            }
+        };
+        if value < 0 {
+            negative = !negative;
+            value = -value;
+        }
+        let mut attach_fraction = true;
+        'units: {
+            if inf && self.scan_keyword("fil")? {
+                order = GlueOrder::Fil;
+                while self.scan_keyword("l")? {
+                    order = order.next().ok_or(illegal_unit("filll"))?;
+                }
+                break 'units;
+            }
+            // Units that are internal dimensions, or em and ex.
+            let t = self.get_x_non_blank()?;
+            let unit = match self.scan_internal(&t)? {
+                Some(Value::Integer(i) | Value::Dimension(i)) if !mu => Some(i),
+                Some(Value::Glue(g)) if !mu => Some(g.width),
+                Some(Value::MuGlue(g)) if mu => Some(g.width),
+                Some(Value::Tokens(_)) => return Err(illegal_unit("pt")),
+                Some(_) => return Err(incompatible_glue_units()),
+                None => {
+                    self.back_input(t)?;
+                    if mu {
+                        None
+                    } else if self.scan_keyword("em")? || self.scan_keyword("ex")? {
+                        // There are no fonts yet, so these are the quad and
+                        // x-height of the null font.
+                        self.scan_optional_space()?;
+                        Some(0)
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let Some(v) = unit {
+                let (f, _) = xn_over_d(v, fraction, UNITY).map_err(|_| dimension_too_large())?;
+                let d = nx_plus_y(value, v, f).map_err(|_| dimension_too_large())?;
+                return self.attach_sign(d, negative);
+            }
+            if mu {
+                if !self.scan_keyword("mu")? {
+                    return Err(illegal_unit("mu"));
+                }
+                break 'units;
+            }
+            if self.scan_keyword("true")? {
+                let mag = self.state.get_integer(IntegerParameter::Mag);
+                if mag != 1000 {
+                    let (v, r) = xn_over_d(value, 1000, mag).map_err(|_| dimension_too_large())?;
+                    let f =
+                        ((1000 * fraction as i64 + UNITY as i64 * r as i64) / mag as i64) as Scaled;
+                    value = v + f / UNITY;
+                    fraction = f % UNITY;
+                }
+            }
+            if self.scan_keyword("pt")? {
+                break 'units;
+            }
+            for (unit, num, denom) in UNITS {
+                if self.scan_keyword(unit)? {
+                    let (v, r) = xn_over_d(value, num, denom).map_err(|_| dimension_too_large())?;
+                    let f = ((num as i64 * fraction as i64 + UNITY as i64 * r as i64)
+                        / denom as i64) as Scaled;
+                    value = v + f / UNITY;
+                    fraction = f % UNITY;
+                    break 'units;
+                }
+            }
+            if self.scan_keyword("sp")? {
+                attach_fraction = false;
+                break 'units;
+            }
+            return Err(illegal_unit("pt"));
+        }
+        if attach_fraction {
+            if value >= 0o40000 {
+                return Err(dimension_too_large());
+            }
+            value = value * UNITY + fraction;
+        }
+        self.scan_optional_space()?;
+        Ok((self.attach_sign(value, negative)?.0, order))
+    }
+    /// Scans the digits after a decimal point, rounding them to a fraction of
+    /// `UNITY`.
+    fn scan_decimals(&mut self) -> Result<Scaled, Error> {
+        let mut digits = Vec::new();
+        loop {
+            let t = self.get_x_token()?;
+            match t {
+                Token::Character(c @ '0'..='9', CharacterCategory::Other) => {
+                    // More than 17 digits cannot change the result.
+                    if digits.len() < 17 {
+                        digits.push(c as u8 - b'0');
+                    }
+                }
+                t => {
+                    if !is_space(&t) {
+                        self.back_input(t)?;
+                    }
+                    return Ok(round_decimals(&digits));
+                }
+            }
+        }
+    }
+    fn attach_sign(&mut self, value: Scaled, negative: bool) -> Result<(Scaled, GlueOrder), Error> {
+        if value.unsigned_abs() >= 0o10000000000 {
+            return Err(dimension_too_large());
+        }
+        Ok((if negative { -value } else { value }, GlueOrder::Normal))
+    }
+
+    /// Scans a glue specification such as `1pt plus 2fil minus 1pt`, in math
+    /// units if `mu` is set.
+    pub fn scan_glue(&mut self, mu: bool) -> Result<Glue, Error> {
+        let (negative, t) = self.scan_signs()?;
+        let width = match self.scan_internal(&t)? {
+            Some(Value::Glue(g)) if !mu => return Ok(if negative { -g } else { g }),
+            Some(Value::MuGlue(g)) if mu => return Ok(if negative { -g } else { g }),
+            Some(Value::Integer(i)) => {
+                let i = if negative { -i } else { i };
+                self.scan_dimension(mu, false, Some(i))?.0
+            }
+            Some(Value::Dimension(d)) if !mu => {
+                if negative {
+                    -d
+                } else {
+                    d
+                }
+            }
+            Some(Value::Tokens(_)) => return Err(missing_number()),
+            Some(_) => return Err(incompatible_glue_units()),
+            None => {
+                self.back_input(t)?;
+                let d = self.scan_dimension(mu, false, None)?.0;
+                if negative {
+                    -d
+                } else {
+                    d
+                }
+            }
+        };
+        let mut glue = Glue {
+            width,
+            ..Glue::default()
+        };
+        if self.scan_keyword("plus")? {
+            (glue.stretch, glue.stretch_order) = self.scan_dimension(mu, true, None)?;
+        }
+        if self.scan_keyword("minus")? {
+            (glue.shrink, glue.shrink_order) = self.scan_dimension(mu, true, None)?;
+        }
+        Ok(glue)
+    }
+}
@@ -0,0 +1,95 @@
+//! Helpers shared by the integration tests. Not every test uses all of them.
+#![allow(dead_code)]
+
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use rutex::{
+    errors::Error,
+    interaction::{InteractionMode, Terminal},
+    parameters::RegisterKind,
+    parser::{
+        lexer::{CharacterCategory, TexFile},
+        parser::Token,
+        symbol::Symbol,
+    },
+    scanning::Value,
+    TexState,
+};
+
+/// A state that reads `source` as the file `test`.
+pub fn state(source: &str) -> Result<TexState, Error> {
+    let mut state = TexState::new();
+    state.add_file(TexFile::new_from_contents(
+        "test".to_string(),
+        source.to_string(),
+    ))?;
+    Ok(state)
+}
+
+/// Executes `source` and returns the resulting state.
+pub fn run(source: &str) -> Result<TexState, Error> {
+    let mut state = state(source)?;
+    state.parse_and_execute()?;
+    Ok(state)
+}
+
+pub fn count(state: &TexState, n: u16) -> Value {
+    state.state.get_register(RegisterKind::Count, n)
+}
+
+pub fn letter(c: char) -> Token {
+    Token::Character(c, CharacterCategory::Letter)
+}
+
+pub fn cs(name: &str) -> Token {
+    Token::ControlSequence(Symbol::intern(name))
+}
+
+/// Output that can still be read after it has been handed over.
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A state that reads `source` in the given interaction mode, with `answers`
+/// typed at the terminal. Returns the state and what the terminal shows.
+pub fn with_terminal(
+    mode: InteractionMode,
+    source: &str,
+    answers: &'static str,
+) -> (TexState, Output) {
+    let output = Output::default();
+    let mut state = state(source).unwrap();
+    state.interaction = mode;
+    state.terminal = Some(Terminal::new(
+        Box::new(answers.as_bytes()),
+        Box::new(output.clone()),
+    ));
+    (state, output)
+}
+
+/// Executes `source` in the given interaction mode with `answers` typed at
+/// the terminal, returning the state, the result and what was shown.
+pub fn interact(
+    mode: InteractionMode,
+    source: &str,
+    answers: &'static str,
+) -> (TexState, Result<(), Error>, String) {
+    let (mut state, output) = with_terminal(mode, source, answers);
+    let result = state.parse_and_execute();
+    (state, result, output.text())
+}
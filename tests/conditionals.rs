@@ -0,0 +1,80 @@
+use rutex::{
+    errors::Error,
+    parser::{lexer::CharacterCategory, parser::Token},
+};
+
+mod common;
+use common::state;
+
+/// Runs `source`, returning the text of all character tokens that reach the
+/// main loop, without the space produced by the end of the line.
+fn expand(source: &str) -> Result<String, Error> {
+    let mut state = state(source)?;
+    let mut output = String::new();
+    while let Ok(token) = state.get_element() {
+        match token {
+            Token::ControlSequence(_)
+            | Token::Character(_, CharacterCategory::BeginGroup | CharacterCategory::EndGroup) => {
+                state.execute_token(token)?
+            }
+            t => output.push_str(&t.to_string()),
+        }
+    }
+    Ok(output.trim_end().to_string())
+}
+
+#[test]
+fn numbers_and_dimensions() -> Result<(), Error> {
+    assert_eq!(expand(r"\ifnum 1<2 a\else b\fi")?, "a");
+    assert_eq!(expand(r"\ifnum 3=2 a\else b\fi")?, "b");
+    assert_eq!(expand(r"\ifnum 3>2 a\fi c")?, "ac");
+    assert_eq!(expand(r"\ifdim 1pt<1.5pt a\else b\fi")?, "a");
+    assert_eq!(expand(r"\ifodd 3 a\else b\fi\ifodd-2 c\else d\fi")?, "ad");
+    assert_eq!(expand(r"\count1=5 \ifnum\count1=5a\fi")?, "a");
+    Ok(())
+}
+
+#[test]
+fn characters_and_meanings() -> Result<(), Error> {
+    assert_eq!(expand(r"\if aay\else n\fi\if ab y\else n\fi")?, "yn");
+    assert_eq!(expand(r"\ifcat aby\else n\fi\ifcat a1y\else n\fi")?, "yn");
+    assert_eq!(expand(r"\def\a{b}\if\a by\else n\fi")?, "y");
+    assert_eq!(
+        expand(r"\def\a{x}\def\b{x}\def\c{y}\ifx\a\b y\else n\fi\ifx\a\c y\else n\fi")?,
+        "yn"
+    );
+    assert_eq!(expand(r"\ifx\undefined\alsoundefined y\else n\fi")?, "y");
+    assert_eq!(expand(r"\ifx\relax\relax y\fi\ifx aay\fi")?, "yy");
+    assert_eq!(
+        expand(r"\long\def\a{x}\def\b{x}\let\c\a\ifx\a\b y\else n\fi\ifx\a\c y\fi")?,
+        "ny"
+    );
+    Ok(())
+}
+
+#[test]
+fn nesting_and_cases() -> Result<(), Error> {
+    assert_eq!(expand(r"\iffalse \iftrue a\else b\fi c\else d\fi")?, "d");
+    assert_eq!(expand(r"\iftrue \iffalse a\else b\fi c\else d\fi")?, "bc");
+    assert_eq!(expand(r"\ifcase 2 a\or b\or c\or d\fi")?, "c");
+    assert_eq!(expand(r"\ifcase 5 a\or b\else e\fi")?, "e");
+    assert_eq!(expand(r"\ifcase 0 a\or b\fi")?, "a");
+    assert_eq!(expand(r"\ifnum 1=1\fi a")?, "a");
+    assert_eq!(
+        expand(r"\ifvmode v\fi\ifhmode h\fi\ifinner i\fi\ifvoid0 o\fi")?,
+        "vo"
+    );
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    assert!(expand(r"\fi")
+        .unwrap_err()
+        .to_string()
+        .contains(r"Extra \fi"));
+    assert!(expand(r"\iftrue a\or b\fi")
+        .unwrap_err()
+        .to_string()
+        .contains(r"Extra \or"));
+}
@@ -0,0 +1,166 @@
+use rutex::{
+    errors::{context_lines, Error, ErrorKind},
+    interaction::{History, InteractionMode},
+    scanning::Value,
+};
+
+mod common;
+use common::{count, interact, run};
+
+/// Executes `source` and returns the error it ends with.
+fn error(source: &str) -> Error {
+    let Err(e) = run(source) else {
+        panic!("no error");
+    };
+    assert!(!matches!(e.kind(), ErrorKind::EndOfFile));
+    e
+}
+
+#[test]
+fn location_and_context() {
+    let e = error("\\def\\a#1{x\\b#1y}\n\\def\\b{\\count1=}\n\\relax\\a{z} more\n");
+    assert_eq!(e.location(), Some(&("test".to_string(), 3, 12)));
+    assert_eq!(
+        e.context(),
+        [
+            "<to be read again> ",
+            "                   z",
+            r"\a ->x\b z",
+            "          y",
+            r"l.3 \relax\a{z}",
+            "                more",
+        ]
+    );
+}
+
+#[test]
+fn long_lines() {
+    let [first, second] = context_lines(&"a".repeat(50), &"b".repeat(50));
+    assert_eq!(first, format!("...{}", "a".repeat(39)));
+    assert_eq!(second, format!("{}{}...", " ".repeat(42), "b".repeat(27)));
+    let [first, second] = context_lines("l.1 short", " rest");
+    assert_eq!(first, "l.1 short");
+    assert_eq!(second, "          rest");
+}
+
+#[test]
+fn scroll_mode() {
+    let (state, result, shown) = interact(InteractionMode::Scroll, r"\count1=x\count2=5 ", "");
+    assert!(result.is_ok());
+    assert_eq!(count(&state, 2), Value::Integer(5));
+    assert_eq!(state.history, History::ErrorMessageIssued);
+    assert!(shown.starts_with("! Missing number, treated as zero.\n<to be read again> \n"));
+}
+
+#[test]
+fn error_stop_mode() {
+    let (state, result, shown) = interact(
+        InteractionMode::ErrorStop,
+        r"\count1=x\count2=5 \count4=y",
+        "H\nI\\count3=7 \nQ\n",
+    );
+    assert!(result.is_ok());
+    assert_eq!(count(&state, 2), Value::Integer(5));
+    assert_eq!(count(&state, 3), Value::Integer(7));
+    assert_eq!(state.interaction, InteractionMode::Batch);
+    assert!(shown.contains("? A number should have been here; I inserted `0'.\n"));
+    assert!(shown.ends_with("? OK, entering \\batchmode...\n"));
+
+    let (_, result, shown) = interact(InteractionMode::ErrorStop, r"\count1=x", "X\n");
+    assert!(matches!(result.unwrap_err().kind(), ErrorKind::JobAborted));
+    assert!(shown.ends_with("*** (job aborted at your request)\n"));
+}
+
+#[test]
+fn interaction_primitives() {
+    let (state, result, shown) = interact(
+        InteractionMode::ErrorStop,
+        r"\batchmode\count1=x\nonstopmode",
+        "",
+    );
+    assert!(result.is_ok());
+    assert!(shown.is_empty());
+    assert_eq!(state.interaction, InteractionMode::NonStop);
+
+    let (state, result, shown) = interact(
+        InteractionMode::ErrorStop,
+        r"\def\a{\count1=x\a}\scrollmode\a",
+        "",
+    );
+    assert!(matches!(result.unwrap_err().kind(), ErrorKind::JobAborted));
+    assert_eq!(state.error_count, 100);
+    assert_eq!(state.history, History::FatalErrorStop);
+    assert!(shown.ends_with("(That makes 100 errors; please try again.)\n"));
+}
+
+#[test]
+fn codes_and_help() {
+    let e = error(r"\undefined");
+    assert_eq!(*e.kind(), ErrorKind::UndefinedControlSequence);
+    assert_eq!(e.kind().code(), 201);
+    assert_eq!(
+        e.help()[0],
+        "The control sequence at the end of the top line"
+    );
+    assert!(e
+        .to_string()
+        .starts_with("test:1:11 [E201] Undefined control sequence\n"));
+    let e = error(r"\def\a#1{}\a\par");
+    assert_eq!(*e.kind(), ErrorKind::ParagraphEnded);
+    assert_eq!(e.kind().code(), 204);
+}
+
+#[test]
+fn errors_in_conditionals() {
+    let (state, result, shown) = interact(
+        InteractionMode::Scroll,
+        r"\ifnum x<1 a\fi\count1=3 \iffalse\or x\fi\count2=4 \ifcase\relax\or\fi\count3=5 ",
+        "",
+    );
+    assert!(result.is_ok());
+    assert!(state.conditions.is_empty());
+    assert_eq!(count(&state, 1), Value::Integer(3));
+    assert_eq!(count(&state, 2), Value::Integer(4));
+    assert_eq!(count(&state, 3), Value::Integer(5));
+    assert!(shown.contains("! Missing number, treated as zero.\n"));
+    assert!(shown.contains("! Extra \\or.\n"));
+}
+
+#[test]
+fn file_ended_in_command() {
+    for source in [r"\count0=", r"\csname abc", r"\ifnum 1<"] {
+        let e = error(source);
+        assert_eq!(*e.kind(), ErrorKind::Runaway, "{}", source);
+    }
+    assert_eq!(
+        error(r"\count0=").message(),
+        r"File ended while scanning use of \count"
+    );
+}
+
+#[test]
+fn number_too_big() {
+    let (state, result, shown) = interact(
+        InteractionMode::Scroll,
+        r"\count1=99999999999\count2=\count1 ",
+        "",
+    );
+    assert!(result.is_ok());
+    assert!(shown.starts_with("! Number too big.\n"));
+    assert_eq!(count(&state, 1), Value::Integer(i32::MAX));
+    assert_eq!(count(&state, 2), Value::Integer(i32::MAX));
+}
+
+#[test]
+fn invalid_codes() {
+    let (state, result, shown) = interact(
+        InteractionMode::Scroll,
+        r#"\catcode`\!=16 \mathchardef\m="8000 \count1=\catcode`\! \count2=\m"#,
+        "",
+    );
+    assert!(result.is_ok());
+    assert!(shown.starts_with("! Invalid code (16), should be between 0 and 15.\n"));
+    assert!(shown.contains("\n! Bad mathchar (32768).\n"));
+    assert_eq!(count(&state, 1), Value::Integer(0));
+    assert_eq!(count(&state, 2), Value::Integer(0));
+}
@@ -0,0 +1,205 @@
+use rutex::{
+    errors::Error,
+    parameters::RegisterKind,
+    parser::{lexer::CharacterCategory, parser::Token, symbol::Symbol},
+    scanning::Value,
+    TexState,
+};
+
+mod common;
+use common::{cs, letter, state};
+
+#[test]
+fn full_expansion() -> Result<(), Error> {
+    let mut s = state(r"\def\a{x}\def\b{y}\def\c{\b z}{\a\noexpand\b\c\relax\count1\iftrue t\fi}")?;
+    for _ in 0..3 {
+        s.parse_and_execute_one()?;
+    }
+    assert_eq!(
+        s.scan_toks(true)?,
+        vec![
+            letter('x'),
+            cs(r"\b"),
+            letter('y'),
+            letter('z'),
+            cs(r"\relax"),
+            cs(r"\count"),
+            Token::Character('1', CharacterCategory::Other),
+            letter('t'),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn unexpanded_text() -> Result<(), Error> {
+    let mut s = state(r"\def\a{x} \relax{\a{\b}}")?;
+    s.parse_and_execute_one()?;
+    assert_eq!(
+        s.scan_toks(false)?,
+        vec![
+            cs(r"\a"),
+            Token::Character('{', CharacterCategory::BeginGroup),
+            cs(r"\b"),
+            Token::Character('}', CharacterCategory::EndGroup),
+        ]
+    );
+    assert!(state("x{}")?.scan_toks(false).is_err());
+    Ok(())
+}
+
+#[test]
+fn noexpand() -> Result<(), Error> {
+    // In the main loop an expandable command after `\noexpand` does nothing.
+    let mut s = state(r"\def\a{\count1=5 }\noexpand\a\a")?;
+    let count = |s: &TexState| s.state.get_register(RegisterKind::Count, 1);
+    for _ in 0..3 {
+        s.parse_and_execute_one()?;
+    }
+    assert_eq!(count(&s), Value::Integer(0));
+    s.parse_and_execute_one()?;
+    s.parse_and_execute_one()?;
+    assert_eq!(count(&s), Value::Integer(5));
+    Ok(())
+}
+
+/// Runs `source` to the end and returns the meaning of `\x`.
+fn meaning_of_x(source: &str) -> Result<String, Error> {
+    let mut s = state(source)?;
+    while s.parse_and_execute_one().is_ok() {}
+    Ok(s.state
+        .get_macro(Symbol::intern(r"\x"))
+        .map(|m| m.meaning())
+        .unwrap_or_default())
+}
+
+#[test]
+fn csname() -> Result<(), Error> {
+    assert_eq!(
+        meaning_of_x(r"\def\ab{y}\edef\x{\csname a\string b\endcsname}")?,
+        "macro:->y"
+    );
+    assert_eq!(
+        meaning_of_x(r"\edef\y{\csname zz\endcsname}\let\x\zz")?,
+        r"\relax"
+    );
+    // The name is only made `\relax` in the current group.
+    let mut s = state(r"{\csname zz\endcsname}")?;
+    while s.parse_and_execute_one().is_ok() {}
+    assert!(s.state.get_macro(Symbol::intern(r"\zz")).is_none());
+    assert!(state(r"\csname a\relax\endcsname")?
+        .parse_and_execute_one()
+        .is_err());
+    // The empty name is not the active backslash.
+    assert_eq!(
+        meaning_of_x(concat!(
+            r"\expandafter\def\csname\endcsname{n}\catcode`\|=0 |catcode`|\=13 ",
+            r"|def\{a}|edef|x{|csname|endcsname\}"
+        ))?,
+        "macro:->na"
+    );
+    Ok(())
+}
+
+#[test]
+fn string_and_meaning() -> Result<(), Error> {
+    assert_eq!(
+        meaning_of_x(r"\edef\x{\string\foo\string a}")?,
+        r"macro:->\fooa"
+    );
+    assert_eq!(
+        meaning_of_x(r"\escapechar=`\! \edef\x{\string\foo}\escapechar=-1 ")?,
+        "macro:->!foo"
+    );
+    assert_eq!(
+        meaning_of_x(r"\long\def\a#1#2{#1\relax##}\edef\x{\meaning\a}")?,
+        r"macro:->\long macro:#1#2->#1\relax ##"
+    );
+    assert_eq!(meaning_of_x(r"\edef\x{\meaning\count}")?, r"macro:->\count");
+    assert_eq!(
+        meaning_of_x(r"\edef\x{\meaning\undefined}")?,
+        "macro:->undefined"
+    );
+    assert_eq!(
+        meaning_of_x(r"\edef\x{\meaning a}")?,
+        "macro:->the letter a"
+    );
+    // The characters have category 12, and spaces category 10.
+    let mut s = state(r"\edef\x{\string\a}\if\x a\else\count1=1 \fi\ifcat\x.\count2=1 \fi")?;
+    while s.parse_and_execute_one().is_ok() {}
+    assert_eq!(
+        s.state.get_register(RegisterKind::Count, 1),
+        Value::Integer(1)
+    );
+    assert_eq!(
+        s.state.get_register(RegisterKind::Count, 2),
+        Value::Integer(1)
+    );
+    Ok(())
+}
+
+#[test]
+fn the() -> Result<(), Error> {
+    assert_eq!(
+        meaning_of_x(r"\count1=-42 \edef\x{\the\count1}")?,
+        "macro:->-42"
+    );
+    assert_eq!(
+        meaning_of_x(r"\dimen0=1.5pt \edef\x{\the\dimen0}")?,
+        "macro:->1.5pt"
+    );
+    assert_eq!(
+        meaning_of_x(r"\skip0=1pt plus 2fil minus 3pt \edef\x{\the\skip0}")?,
+        "macro:->1.0pt plus 2.0fil minus 3.0pt"
+    );
+    assert_eq!(
+        meaning_of_x(r"\muskip0=1mu \edef\x{\the\muskip0}")?,
+        "macro:->1.0mu"
+    );
+    assert_eq!(meaning_of_x(r"\edef\x{\the\catcode`\{}")?, "macro:->1");
+    // Token lists are not expanded any further inside `\edef`.
+    assert_eq!(
+        meaning_of_x(r"\def\a{b}\toks0={\a c}\edef\x{\the\toks0 \a}")?,
+        r"macro:->\a cb"
+    );
+    assert!(state(r"\the\relax")?.parse_and_execute_one().is_err());
+    Ok(())
+}
+
+#[test]
+fn expandafter() -> Result<(), Error> {
+    assert_eq!(
+        meaning_of_x(r"\expandafter\def\csname x\endcsname{y}")?,
+        "macro:->y"
+    );
+    assert_eq!(
+        meaning_of_x(r"\def\a{\b}\def\b{c}\edef\x{\expandafter\noexpand\a}")?,
+        r"macro:->\b "
+    );
+    assert_eq!(
+        meaning_of_x(r"\def\a{b}\edef\x{\expandafter\noexpand\noexpand\a}")?,
+        r"macro:->\a "
+    );
+    Ok(())
+}
+
+#[test]
+fn after_assignment_and_group() -> Result<(), Error> {
+    assert_eq!(
+        meaning_of_x(r"\def\a{\def\x{y}}\afterassignment\a\def\x{z}")?,
+        "macro:->y"
+    );
+    assert_eq!(
+        meaning_of_x(r"\def\a{\def\x{y}}\afterassignment\a\count1=5 ")?,
+        "macro:->y"
+    );
+    assert_eq!(
+        meaning_of_x(r"\def\a{\def\x{1}}\def\b{\edef\x{\x2}}{\aftergroup\a\aftergroup\b}")?,
+        "macro:->12"
+    );
+    assert_eq!(
+        meaning_of_x(r"\let\egroup=}\def\x{o}{\def\x{a}{\aftergroup\egroup}\edef\x{\x b}")?,
+        "macro:->ob"
+    );
+    Ok(())
+}
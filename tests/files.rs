@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+
+use rutex::{
+    errors::{Error, ErrorKind},
+    interaction::InteractionMode,
+    parser::lexer::TexFile,
+    scanning::Value,
+    TexState,
+};
+
+mod common;
+use common::{count, with_terminal};
+
+/// A fresh directory for the files of one test.
+fn directory(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rutex-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    dir
+}
+
+/// Executes `source` with `dir` on the search path.
+fn run(dir: &Path, source: &str) -> Result<TexState, Error> {
+    let mut state = common::state(source)?;
+    state.search_path = vec![dir.to_path_buf()];
+    state.parse_and_execute()?;
+    Ok(state)
+}
+
+#[test]
+fn input_and_endinput() -> Result<(), Error> {
+    let dir = directory("input");
+    std::fs::write(
+        dir.join("sub/inc.tex"),
+        "\\count1=\\inputlineno\\relax\n\\count2=8 \\endinput \\count3=9\n\\count2=100\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("plain"), "\\count5=5\n").unwrap();
+    let state = run(
+        &dir,
+        "\n\\input {sub/inc}\\count4=\\inputlineno\\relax\n\\input plain \\count6=6\n",
+    )?;
+    assert_eq!(count(&state, 1), Value::Integer(1));
+    assert_eq!(count(&state, 2), Value::Integer(8));
+    assert_eq!(count(&state, 3), Value::Integer(9));
+    assert_eq!(count(&state, 4), Value::Integer(2));
+    assert_eq!(count(&state, 5), Value::Integer(5));
+    assert_eq!(count(&state, 6), Value::Integer(6));
+    Ok(())
+}
+
+#[test]
+fn search_path() {
+    let dir = directory("search");
+    let candidates = TexFile::candidates("story", std::slice::from_ref(&dir));
+    assert_eq!(
+        candidates,
+        vec![
+            PathBuf::from("story.tex"),
+            PathBuf::from("story"),
+            dir.join("story.tex"),
+            dir.join("story"),
+        ]
+    );
+    let error = run(&dir, "\\input missing ").err().unwrap();
+    assert!(matches!(error.kind(), ErrorKind::FileNotFound));
+}
+
+#[test]
+fn input_levels() {
+    let dir = directory("levels");
+    std::fs::write(dir.join("loop.tex"), "\\input loop\n").unwrap();
+    let error = run(&dir, "\\input loop ").err().unwrap();
+    assert!(error
+        .to_string()
+        .contains("TeX capacity exceeded, sorry [text input levels=6]"));
+}
+
+#[test]
+fn missing_file_prompt() {
+    let dir = directory("prompt");
+    std::fs::write(dir.join("there.tex"), "\\count1=1\n").unwrap();
+    let run_in = |mode: InteractionMode, answers: &'static str| {
+        let (mut state, _) = with_terminal(mode, r"\input nothere \count2=2 ", answers);
+        state.search_path = vec![dir.clone()];
+        let result = state.parse_and_execute();
+        (state, result)
+    };
+    let (state, result) = run_in(InteractionMode::NonStop, "");
+    assert!(matches!(result.unwrap_err().kind(), ErrorKind::JobAborted));
+    assert_eq!(count(&state, 2), Value::Integer(0));
+
+    let (state, result) = run_in(InteractionMode::Scroll, "there\n");
+    assert!(result.is_ok());
+    assert_eq!(count(&state, 1), Value::Integer(1));
+    assert_eq!(count(&state, 2), Value::Integer(2));
+}
@@ -0,0 +1,39 @@
+use rutex::errors::Error;
+
+mod common;
+use common::run;
+
+fn error(source: &str) -> String {
+    run(source).err().expect("an error").to_string()
+}
+
+#[test]
+fn matching_groups() -> Result<(), Error> {
+    let state = run(r"{\begingroup\let\bgroup={\bgroup}\endgroup}")?;
+    assert!(state.groups.is_empty());
+    assert!(state.final_cleanup().is_empty());
+    Ok(())
+}
+
+#[test]
+fn mismatched_groups() {
+    assert!(error(r"\begingroup}").contains(r"Extra }, or forgotten \endgroup"));
+    assert!(error(r"{\endgroup").contains("Missing } inserted"));
+    assert!(error("}").contains("Too many }'s"));
+    assert!(error(r"\endgroup").contains(r"Extra \endgroup"));
+}
+
+#[test]
+fn unclosed_groups() -> Result<(), Error> {
+    let state = run("{\n\\begingroup\\iftrue\n")?;
+    assert_eq!(
+        state.final_cleanup(),
+        vec![
+            r"(\end occurred inside a group at level 2)",
+            "### semi simple group (level 2) entered at line 2",
+            "### simple group (level 1) entered at line 1",
+            r"(\end occurred when \iftrue on line 2 was incomplete)",
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use rutex::{
+    errors::Error,
+    parser::{lexer::CharacterCategory, parser::Token},
+};
+
+mod common;
+use common::state;
+
+/// Runs `source`, returning the text of all character tokens that reach the
+/// main loop (braces included), without the space produced by the end of the
+/// line.
+fn expand(source: &str) -> Result<String, Error> {
+    let mut state = state(source)?;
+    let mut output = String::new();
+    while let Ok(token) = state.get_element() {
+        match token {
+            Token::ControlSequence(_) => state.execute_token(token)?,
+            Token::Character(_, CharacterCategory::BeginGroup | CharacterCategory::EndGroup) => {
+                output.push_str(&token.to_string());
+                state.execute_token(token)?
+            }
+            t => output.push_str(&t.to_string()),
+        }
+    }
+    Ok(output.trim_end().to_string())
+}
+
+#[test]
+fn undelimited_parameters() -> Result<(), Error> {
+    assert_eq!(expand(r"\def\a#1#2{(#2,#1)}\a x{yz}")?, "(yz,x)");
+    assert_eq!(expand(r"\def\a#1{[#1]}\a{{x}}")?, "[{x}]");
+    Ok(())
+}
+
+#[test]
+fn delimited_parameters() -> Result<(), Error> {
+    assert_eq!(expand(r"\def\a#1.#2\end{[#1|#2]}\a{x}.y.z\end")?, "[x|y.z]");
+    assert_eq!(expand(r"\def\a#1.{[#1]}\a{x}{y}.")?, "[{x}{y}]");
+    assert_eq!(expand(r"\def\a#1aab{[#1]}\a xaaab")?, "[xa]");
+    assert_eq!(expand(r"\def\a<#1>{[#1]}\a<{>}>")?, "[>]");
+    Ok(())
+}
+
+#[test]
+fn parameter_characters() -> Result<(), Error> {
+    assert_eq!(expand(r"\def\a#1#{[#1]}\a x{")?, "[x]{");
+    assert_eq!(expand(r"\def\a{\def\b##1{<##1>}}\a\b z")?, "<z>");
+    Ok(())
+}
+
+#[test]
+fn mismatched_use() {
+    let error = expand(r"\def\a.{}\a,").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains(r"Use of \a doesn't match its definition"));
+    assert!(expand(r"\def\a#2{}").is_err());
+}
+
+#[test]
+fn input_stack_limit() {
+    let error = expand(r"\def\a{\a x}\a").unwrap_err();
+    assert!(error.to_string().contains("input stack size"));
+}
+
+#[test]
+fn catcode_assignments() -> Result<(), Error> {
+    assert_eq!(expand(r"{\catcode`\!=0 !def!a{x}!a}!")?, "{x}!");
+    assert_eq!(expand(r"{{\global\catcode`\!=0 }}!def!a{y}!a")?, "{{}}y");
+    assert_eq!(
+        expand(r"{\catcode`\!=0 {\global\catcode`\!=12 }!}!")?,
+        "{{}!}!"
+    );
+    assert_eq!(expand(r"{\globaldefs=1 \catcode`\!=0 }!def!a{z}!a")?, "{}z");
+    assert_eq!(expand(r"\begingroup\catcode`\!=0 \endgroup!")?, "!");
+    assert_eq!(expand(r"\catcode`\^^M=13 \def^^M{x}")?, "x");
+    assert!(expand(r"\catcode`\!=16").is_err());
+    Ok(())
+}
+
+#[test]
+fn definition_family() -> Result<(), Error> {
+    assert_eq!(expand(r"\def\a{x}\def\b{y}\edef\c{\a\b}\def\a{z}\c")?, "xy");
+    assert_eq!(expand(r"\def\a{x}\edef\c#1{#1\a##}\c y")?, "yx#");
+    assert_eq!(expand(r"{\gdef\a{x}}\a")?, "{}x");
+    assert_eq!(expand(r"\def\b{y}{\xdef\a{\b}}\def\b{z}\a")?, "{}y");
+    assert!(expand(r"{\def\a{x}}\a").is_err());
+    Ok(())
+}
+
+#[test]
+fn prefixes() -> Result<(), Error> {
+    assert_eq!(expand(r"\long\def\a#1{[#1]}\a{x\par y}")?, "[xy]");
+    let error = expand(r"\def\a#1{}\a{x\par}").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains(r"Paragraph ended before \a was complete"));
+    assert_eq!(
+        expand(r"\protected\def\p{x}\edef\a{\p y}\def\p{z}\a")?,
+        "zy"
+    );
+    assert_eq!(expand(r"\outer\def\o{x}\o")?, "x");
+    let error = expand(r"\outer\def\o{}\def\a#1{}\a\o").unwrap_err();
+    assert!(error.to_string().contains("Forbidden control sequence"));
+    let error = expand(r"\outer\def\o{}\def\a{\o}").unwrap_err();
+    assert!(error.to_string().contains("Forbidden control sequence"));
+    let error = expand(r"\outer\def\o{}\iffalse\o\fi").unwrap_err();
+    assert!(error.to_string().contains(r"Incomplete \iffalse"));
+    let error = expand(r"\long\count1=1").unwrap_err();
+    assert!(error.to_string().contains(r"You can't use `\long'"));
+    let error = expand(r"\global\relax x").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("You can't use a prefix with `x'"));
+    Ok(())
+}
+
+#[test]
+fn let_and_futurelet() -> Result<(), Error> {
+    assert_eq!(expand(r"\def\a{x}\let\b=\a\def\a{y}\b\a")?, "xy");
+    assert_eq!(expand(r"\let\b= =\if\b=y\fi")?, "y");
+    assert_eq!(
+        expand(r"\let\bgroup={\let\egroup=}\def\a{x}\bgroup\def\a{y}\egroup\a")?,
+        "x"
+    );
+    assert_eq!(
+        expand(r"\let\bgroup={\ifx\bgroup{y\fi\ifcat\bgroup{y\fi")?,
+        "yy"
+    );
+    assert_eq!(expand(r"\let\a\relax\ifx\a\relax y\fi")?, "y");
+    assert_eq!(expand(r"\let\a\undefined\ifx\a\alsoundefined y\fi")?, "y");
+    assert_eq!(
+        expand(r"\def\a{\ifx\next x[\fi}\futurelet\next\a xy")?,
+        "[xy"
+    );
+    Ok(())
+}
+
+#[test]
+fn character_definitions() -> Result<(), Error> {
+    assert_eq!(expand(r"\chardef\c=65 \ifnum\c=65 y\fi")?, "y");
+    assert_eq!(expand(r#"\mathchardef\m="7141 \ifnum\m=28993 y\fi"#)?, "y");
+    assert!(expand(r#"\mathchardef\m="8000"#)
+        .unwrap_err()
+        .to_string()
+        .contains("Bad mathchar"));
+    assert!(expand(r#"\mathchardef\m="41 \m"#)
+        .unwrap_err()
+        .to_string()
+        .contains("Missing $ inserted"));
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use rutex::{
+    errors::Error,
+    parameters::RegisterKind,
+    parser::{lexer::CharacterCategory, parser::Token},
+    scanning::{
+        arithmetic::{Glue, GlueOrder},
+        Value,
+    },
+};
+
+mod common;
+use common::{count, run};
+
+#[test]
+fn assignments() -> Result<(), Error> {
+    let state = run(concat!(
+        "\\count1=-7 \\count2 \\count1 \\dimen0=1.5pt \\dimen1=2\\dimen0 ",
+        "\\skip3=1pt plus 1fil \\muskip4=3mu minus 1mu \\toks5={a{b}}\\toks6\\toks5 "
+    ))?;
+    assert_eq!(count(&state, 1), Value::Integer(-7));
+    assert_eq!(count(&state, 2), Value::Integer(-7));
+    assert_eq!(
+        state.state.get_register(RegisterKind::Dimen, 1),
+        Value::Dimension(3 * 65536)
+    );
+    assert_eq!(
+        state.state.get_register(RegisterKind::Skip, 3),
+        Value::Glue(Glue {
+            width: 65536,
+            stretch: 65536,
+            stretch_order: GlueOrder::Fil,
+            ..Glue::default()
+        })
+    );
+    assert_eq!(
+        state.state.get_register(RegisterKind::MuSkip, 4),
+        Value::MuGlue(Glue {
+            width: 3 * 65536,
+            shrink: 65536,
+            ..Glue::default()
+        })
+    );
+    let tokens = vec![
+        Token::Character('a', CharacterCategory::Letter),
+        Token::Character('{', CharacterCategory::BeginGroup),
+        Token::Character('b', CharacterCategory::Letter),
+        Token::Character('}', CharacterCategory::EndGroup),
+    ];
+    assert_eq!(
+        state.state.get_register(RegisterKind::Toks, 6),
+        Value::Tokens(tokens)
+    );
+    Ok(())
+}
+
+#[test]
+fn grouping() -> Result<(), Error> {
+    let state = run("\\count1=1 {\\count1=2 \\global\\count2=3 \\count3=4 }")?;
+    assert_eq!(count(&state, 1), Value::Integer(1));
+    assert_eq!(count(&state, 2), Value::Integer(3));
+    assert_eq!(count(&state, 3), Value::Integer(0));
+    // A global assignment survives the end of every group around it, even
+    // when it is followed by local ones.
+    let state = run("{\\count1=1 {\\global\\count1=2 {\\count1=3 }}}")?;
+    assert_eq!(count(&state, 1), Value::Integer(2));
+    let state = run("{\\count1=1 \\global\\count1=2 \\count1=3 }")?;
+    assert_eq!(count(&state, 1), Value::Integer(2));
+    let state = run(&format!(
+        "{}\\count1=7 {}",
+        "{".repeat(5000),
+        "}".repeat(5000)
+    ))?;
+    assert_eq!(count(&state, 1), Value::Integer(0));
+    Ok(())
+}
+
+#[test]
+fn register_definitions() -> Result<(), Error> {
+    let state = run(
+        "{\\global\\countdef\\foo=10 }\\foo=5 \\count11=\\foo \\dimendef\\bar 2 \\bar=\\foo pt ",
+    )?;
+    assert_eq!(count(&state, 10), Value::Integer(5));
+    assert_eq!(count(&state, 11), Value::Integer(5));
+    assert_eq!(
+        state.state.get_register(RegisterKind::Dimen, 2),
+        Value::Dimension(5 * 65536)
+    );
+    let e = run("\\count32768=1 ").err().unwrap();
+    assert!(e.to_string().contains("Bad register code (32768)"));
+    Ok(())
+}
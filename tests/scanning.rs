@@ -0,0 +1,103 @@
+use rutex::{
+    errors::{Error, ErrorKind},
+    scanning::arithmetic::{round_decimals, xn_over_d, Glue, GlueOrder},
+    TexState,
+};
+
+mod common;
+
+/// A state reading `source`, followed by `\relax` so that keywords can be
+/// looked for after the last quantity.
+fn state(source: &str) -> Result<TexState, Error> {
+    common::state(&format!("{} \\relax", source))
+}
+
+fn dimen(source: &str) -> Result<i32, Error> {
+    state(source)?.scan_dimen()
+}
+
+#[test]
+fn integers() -> Result<(), Error> {
+    let mut s = state("-+-42 '777 \"1F `a `\\% 2147483647")?;
+    assert_eq!(s.scan_int()?, 42);
+    assert_eq!(s.scan_int()?, 0o777);
+    assert_eq!(s.scan_int()?, 0x1f);
+    assert_eq!(s.scan_int()?, 'a' as i32);
+    assert_eq!(s.scan_int()?, '%' as i32);
+    assert_eq!(s.scan_int()?, i32::MAX);
+    let mut s = state("99999999999")?;
+    let e = s.scan_int().unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::NumberTooBig));
+    // All the digits are used up.
+    assert_eq!(s.get_x_non_blank()?.to_string(), r"\relax");
+    let e = state("x")?.scan_int().unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::MissingNumber));
+    Ok(())
+}
+
+#[test]
+fn units() -> Result<(), Error> {
+    assert_eq!(dimen("1.5pt")?, 98304);
+    assert_eq!(dimen("1,5 PT")?, 98304);
+    assert_eq!(dimen("-.5pt")?, -32768);
+    assert_eq!(dimen("1in")?, 4736286);
+    assert_eq!(dimen("1pc")?, 786432);
+    assert_eq!(dimen("1cm")?, 1864679);
+    assert_eq!(dimen("1mm")?, 186467);
+    assert_eq!(dimen("1bp")?, 65781);
+    assert_eq!(dimen("1dd")?, 70124);
+    assert_eq!(dimen("1cc")?, 841489);
+    assert_eq!(dimen("'10sp")?, 8);
+    assert_eq!(dimen("3em")?, 0);
+    assert_eq!(dimen("2\\mag")?, 2000);
+    let e = dimen("3 xyz").unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::IllegalUnit));
+    let e = dimen("16384pt").unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::DimensionTooLarge));
+    Ok(())
+}
+
+#[test]
+fn true_dimensions() -> Result<(), Error> {
+    let mut s = state("\\mag=2000 1truein 1in")?;
+    s.parse_and_execute_one()?;
+    assert_eq!(s.scan_dimen()?, 2368143);
+    assert_eq!(s.scan_dimen()?, 4736286);
+    Ok(())
+}
+
+#[test]
+fn glue() -> Result<(), Error> {
+    let mut s = state("1pt plus 2fil minus 3fill -2pt plus -1pt 1pt plus 1fil")?;
+    assert_eq!(
+        s.scan_glue(false)?,
+        Glue {
+            width: 65536,
+            stretch: 2 * 65536,
+            stretch_order: GlueOrder::Fil,
+            shrink: 3 * 65536,
+            shrink_order: GlueOrder::Fill,
+        }
+    );
+    assert_eq!(
+        s.scan_glue(false)?,
+        Glue {
+            width: -2 * 65536,
+            stretch: -65536,
+            ..Glue::default()
+        }
+    );
+    assert_eq!(s.scan_glue(false)?.format("pt"), "1.0pt plus 1.0fil");
+    let e = state("1pt plus 1fillll")?.scan_glue(false).unwrap_err();
+    assert!(matches!(e.kind(), ErrorKind::IllegalUnit));
+    Ok(())
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(round_decimals(&[5]), 32768);
+    assert_eq!(round_decimals(&[1]), 6554);
+    assert_eq!(xn_over_d(7, 3, 2), Ok((10, 1)));
+    assert_eq!(xn_over_d(-7, 3, 2), Ok((-10, -1)));
+    assert!(xn_over_d(1 << 29, 4, 2).is_err());
+}
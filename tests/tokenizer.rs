@@ -0,0 +1,202 @@
+use rutex::{
+    errors::{Error, ErrorKind},
+    parser::{
+        lexer::{CharacterCategory, TexFile},
+        parser::Token,
+        symbol::Symbol,
+    },
+    parsing::Input,
+    TexState,
+};
+
+mod common;
+use common::{cs, letter, state};
+
+/// Reads all tokens of `source` without expanding or executing them.
+fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let mut state = state(source)?;
+    let mut tokens = vec![];
+    loop {
+        match state.get_element() {
+            Ok(token) => tokens.push(token),
+            Err(e) if matches!(e.kind(), ErrorKind::EndOfFile) => return Ok(tokens),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn space() -> Token {
+    Token::Character(' ', CharacterCategory::Space)
+}
+
+#[test]
+fn spaces_collapse() -> Result<(), Error> {
+    assert_eq!(
+        tokenize("  a \t  b   \n")?,
+        vec![letter('a'), space(), letter('b'), space()]
+    );
+    Ok(())
+}
+
+#[test]
+fn empty_lines_are_par() -> Result<(), Error> {
+    assert_eq!(
+        tokenize("a\n\n   \nb")?,
+        vec![
+            letter('a'),
+            space(),
+            cs(r"\par"),
+            cs(r"\par"),
+            letter('b'),
+            space()
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn control_sequences_skip_blanks() -> Result<(), Error> {
+    assert_eq!(
+        tokenize("\\foo   a\\   b\\1 c")?,
+        vec![
+            cs(r"\foo"),
+            letter('a'),
+            cs(r"\ "),
+            letter('b'),
+            cs(r"\1"),
+            space(),
+            letter('c'),
+            space()
+        ]
+    );
+    assert_eq!(
+        tokenize("\\foo\nb")?,
+        vec![cs(r"\foo"), letter('b'), space()]
+    );
+    Ok(())
+}
+
+#[test]
+fn comments_end_the_line() -> Result<(), Error> {
+    assert_eq!(
+        tokenize("a% comment\n  b")?,
+        vec![letter('a'), letter('b'), space()]
+    );
+    Ok(())
+}
+
+#[test]
+fn superscript_notation() -> Result<(), Error> {
+    assert_eq!(
+        tokenize("^^41^^5c^^7a^^^^00e9^^^^^^01f600")?,
+        vec![
+            letter('A'),
+            cs(r"\z"),
+            Token::Character('\u{e9}', CharacterCategory::Other),
+            Token::Character('\u{1f600}', CharacterCategory::Other),
+            space()
+        ]
+    );
+    assert!(tokenize("^^?").is_err());
+    assert_eq!(tokenize("\\f^^6f^^6fx")?, vec![cs(r"\foox")]);
+    assert_eq!(
+        tokenize("a^^M b")?,
+        vec![letter('a'), space()],
+        "^^M ends the line"
+    );
+    assert_eq!(
+        tokenize("^^z^b")?,
+        vec![
+            Token::Character('\u{3a}', CharacterCategory::Other),
+            Token::Character('^', CharacterCategory::Superscript),
+            letter('b'),
+            space()
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn interned_names() -> Result<(), Error> {
+    let tokens = tokenize(r"\foo\foo\bar")?;
+    assert_eq!(tokens[0], tokens[1]);
+    assert_ne!(tokens[0], tokens[2]);
+    assert_eq!(tokens[0], cs(r"\foo"));
+    assert_eq!(Symbol::intern(r"\foo").name(), r"\foo");
+    assert_eq!(tokens[2].to_string(), r"\bar");
+    Ok(())
+}
+
+#[test]
+fn non_ascii_positions() -> Result<(), Error> {
+    let mut state = state("x\näöü∑\\é y")?;
+    let next = |state: &mut TexState| -> Result<(Token, (usize, usize), usize), Error> {
+        let t = state.get_element()?;
+        let file = state.input.current_file().unwrap();
+        Ok((t, file.get_text_position(), file.byte_offset()))
+    };
+    next(&mut state)?;
+    next(&mut state)?;
+    let other = |c| Token::Character(c, CharacterCategory::Other);
+    assert_eq!(next(&mut state)?, (other('ä'), (2, 2), 4));
+    next(&mut state)?;
+    next(&mut state)?;
+    assert_eq!(next(&mut state)?, (other('∑'), (2, 5), 11));
+    assert_eq!(next(&mut state)?, (cs(r"\é"), (2, 7), 14));
+    Ok(())
+}
+
+#[test]
+fn positions_after_superscripts() -> Result<(), Error> {
+    let mut state = state("\\f^^6f^^6fx y")?;
+    assert_eq!(state.get_element()?, cs(r"\foox"));
+    let file = state.input.current_file().unwrap();
+    assert_eq!(file.get_text_position(), (1, 12));
+    assert_eq!(file.byte_offset(), 11);
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8() {
+    let mut state = TexState::new();
+    let input = Input::new_from_source("test", Box::new(&b"ok\n\xff\n"[..]));
+    state.add_file(TexFile::new_from_input(input)).unwrap();
+    assert!(state.get_element().is_ok());
+    let error = loop {
+        match state.get_element() {
+            Ok(_) => continue,
+            Err(e) => break e,
+        }
+    };
+    assert!(matches!(error.kind(), ErrorKind::InvalidUtf8));
+    assert!(error.to_string().starts_with("test:2:1 "));
+    assert!(error.to_string().contains("Invalid UTF-8 at byte 3"));
+}
+
+#[test]
+fn missing_file() {
+    let error = match TexFile::open("does/not/exist.tex", &[]) {
+        Ok(_) => panic!("opened a missing file"),
+        Err(e) => e,
+    };
+    assert!(matches!(error.kind(), ErrorKind::FileNotFound));
+    assert!(error
+        .to_string()
+        .contains("I can't find file `does/not/exist.tex' (tried does/not/exist.tex)"));
+}
+
+#[test]
+fn line_numbers_after_invalid_utf8() {
+    let mut state = TexState::new();
+    let input = Input::new_from_source("test", Box::new(&b"ab\xffcd\nx\n"[..]));
+    state.add_file(TexFile::new_from_input(input)).unwrap();
+    let Err(e) = state.get_element() else {
+        panic!("no error");
+    };
+    assert!(matches!(e.kind(), ErrorKind::InvalidUtf8));
+    assert!(e.to_string().starts_with("test:1:3 "));
+    // The valid part of the line is still read.
+    assert_eq!(state.get_element().unwrap(), letter('a'));
+    while state.get_element().unwrap() != letter('x') {}
+    assert_eq!(state.input.current_file().unwrap().get_text_position().0, 2);
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use rutex::{
+    errors::ErrorKind,
+    interaction::{format_date, History, InteractionMode, Printer},
+    TexState,
+};
+
+mod common;
+use common::{with_terminal, Output};
+
+/// Executes `source` in the given interaction mode, returning the state,
+/// what was shown on the terminal and what was written to the transcript.
+fn run(mode: InteractionMode, source: &str) -> (TexState, String, String) {
+    let (mut state, terminal) = with_terminal(mode, source, "");
+    let log = Output::default();
+    state.open_log(Box::new(log.clone()), "rutex test");
+    assert!(state.parse_and_execute().is_ok());
+    state.close_log();
+    (state, terminal.text(), log.text())
+}
+
+#[test]
+fn line_breaking() {
+    let output = Output::default();
+    let mut printer = Printer::new(Box::new(output.clone()));
+    printer.print(&"x".repeat(100));
+    assert_eq!(printer.offset(), 21);
+    printer.start_line();
+    printer.start_line();
+    printer.print("a\nb");
+    printer.print_ln();
+    assert_eq!(
+        output.text(),
+        format!("{}\n{}\na\nb\n", "x".repeat(79), "x".repeat(21))
+    );
+}
+
+#[test]
+fn date() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_792_332_300);
+    assert_eq!(format_date(time), "18 OCT 2026 14:05");
+    assert_eq!(format_date(UNIX_EPOCH), "1 JAN 1970 00:00");
+}
+
+#[test]
+fn errors_in_batch_mode() {
+    let (state, shown, log) = run(InteractionMode::Batch, r"\count1=x");
+    assert!(shown.is_empty());
+    assert_eq!(state.history, History::ErrorMessageIssued);
+    let mut lines = log.lines();
+    assert_eq!(lines.next(), Some("rutex test"));
+    assert!(lines.next().unwrap().starts_with("(INITEX)  "));
+    assert!(log.contains(
+        "! Missing number, treated as zero.\n\
+         <to be read again> \n"
+    ));
+    assert!(log.contains("A number should have been here; I inserted `0'.\n"));
+}
+
+#[test]
+fn tracing_macros() {
+    let source = r"\tracingmacros=1 \def\a#1#2{#2#1}\a x{yz}";
+    let (state, shown, log) = run(InteractionMode::Scroll, source);
+    assert!(shown.is_empty());
+    assert_eq!(state.history, History::WarningIssued);
+    assert!(log.ends_with("\n\\a #1#2->#2#1\n#1<-x\n#2<-yz\n"));
+
+    let (_, shown, _) = run(
+        InteractionMode::Scroll,
+        &format!(r"\tracingonline=1 {}", source),
+    );
+    assert_eq!(shown, "\n\\a #1#2->#2#1\n#1<-x\n#2<-yz\n");
+}
+
+#[test]
+fn messages() {
+    let long = "x".repeat(70);
+    let (_, shown, log) = run(
+        InteractionMode::Scroll,
+        &format!(
+            r"\def\a{{A}}\message{{Hello \a}}\message{{again}}\message{{{}}}",
+            long
+        ),
+    );
+    assert_eq!(shown, format!("Hello A again\n{}", long));
+    assert!(log.ends_with(&format!("\nHello A again\n{}\n", long)));
+}
+
+#[test]
+fn show() {
+    let (state, shown, log) = run(
+        InteractionMode::Scroll,
+        r"\def\a#1{x#1}\count1=5 \show\a\showthe\count1 \show a",
+    );
+    assert!(shown.starts_with("> \\a=macro:\n#1->x#1.\nl.1 \\def\\a#1{x#1}\\count1=5 \\show\\a\n"));
+    assert!(shown.contains("\n> 5.\n"));
+    assert!(shown.contains("\n> the letter a.\n"));
+    assert!(!log.contains("showing something"));
+    assert_eq!(state.history, History::ErrorMessageIssued);
+    assert_eq!(state.error_count, 0);
+
+    let (_, shown, log) = run(InteractionMode::Scroll, r"\showbox0 \showlists");
+    assert!(shown.starts_with("! OK (see the transcript file).\n"));
+    assert!(log.contains("\n> \\box0=void\n\n! OK.\n"));
+    assert!(!log.contains("see the transcript file"));
+    assert!(log.contains("\n### vertical mode\n"));
+}
+
+#[test]
+fn unterminated_message() {
+    let Err(e) = common::run(r"\message{abc") else {
+        panic!("no error");
+    };
+    assert_eq!(*e.kind(), ErrorKind::Runaway);
+    assert_eq!(e.message(), r"File ended while scanning text of \message");
+}
+
+#[test]
+fn errmessage() {
+    let (_, shown, log) = run(
+        InteractionMode::Scroll,
+        r"\errmessage{Oops}\errmessage{Oops again}{\errhelp{Read \the\count1}\errmessage{Oops}}",
+    );
+    assert!(shown.starts_with("! Oops.\nl.1 \\errmessage{Oops}\n"));
+    assert!(log.contains("Pretend that you're Hercule Poirot"));
+    assert!(log.contains("\n(That was another \\errmessage.)\n"));
+    assert!(log.contains("\nRead \\the \\count 1\n"));
+}
@@ -1,6 +1,6 @@
 use std::io::Read;
 
-use rutex::parsing::{Input, InputResult};
+use rutex::parsing::Input;
 struct Utf8Test {
     name: String,
     is_valid: bool,
@@ -1161,11 +1161,12 @@ fn test() -> Result<(), ()> {
     for test in tests.iter() {
         let mut inp =
             Input::new_from_source(&test.name, Box::new(ReadBuf::new(test.input.clone())));
+        let mut line = Vec::new();
         let result = loop {
-            match inp.read_char() {
-                InputResult::Char(_) => continue,
-                InputResult::Eof => break true,
-                InputResult::Error(_) => break false,
+            match inp.read_line(&mut line) {
+                Ok(true) => continue,
+                Ok(false) => break true,
+                Err(_) => break false,
             }
         };
 